@@ -1,42 +1,596 @@
 use std::{
-    ffi::OsStr,
+    cmp::Ordering as CmpOrdering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::{BufRead, BufWriter, Write},
     path::{Path, PathBuf},
-    str::FromStr,
+    process::Command,
     sync::{
-        mpsc::{channel, Sender},
-        Arc, LazyLock, Mutex,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, TryRecvError},
+        Arc, Condvar, LazyLock, Mutex,
     },
     thread,
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{anyhow, Result};
 use candle_core::Device;
-use eframe::egui::{self, TextEdit};
+use eframe::egui::{self, Color32, TextEdit};
+use egui_extras::{Column, TableBuilder};
 use egui_file::FileDialog;
+use lora_view::gguf::read_gguf_header;
+use lora_view::metadata::{
+    content_fingerprint, data_sha256, has_extension, has_safetensors_extension, header_bytes,
+    read_header, read_zip_header, zip_entries, LoraData, ModelType, ParseOptions,
+};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
 
-use crate::metadata::{read_header, LoraData};
+use crate::cache::MetadataCache;
+use crate::config::Config;
 
-type MetadataRecord = (
-    PathBuf,
-    LazyLock<LoraData, Box<dyn FnOnce() -> LoraData + Send + Sync + 'static>>,
-);
+/// A model in the current store, parsed on first access
+struct MetadataRecord {
+    path: PathBuf,
+    /// File modification time and size, if the path is a real file
+    modified: Option<SystemTime>,
+    size: Option<u64>,
+    /// Preview image next to the file, as downloaded from Civitai
+    preview: Option<PathBuf>,
+    data: LazyLock<Arc<LoraData>, Box<dyn FnOnce() -> Arc<LoraData> + Send + Sync + 'static>>,
+    /// Set once `data` has been forced by [`MetadataRecord::force`]
+    loaded: AtomicBool,
+}
 type MetadataStore = Arc<Vec<MetadataRecord>>;
 
-fn metadata_record(path: &Path) -> MetadataRecord {
-    let path = path.to_path_buf();
-    (
-        path.clone(),
-        LazyLock::new(Box::new(move || {
-            if let Ok(buffer) = read_header(&path) {
-                LoraData::from_buffer(&buffer).unwrap_or_default()
+impl MetadataRecord {
+    /// Create a record which is parsed with `read` on first access, unless it's found in `cache`
+    fn new(
+        path: PathBuf,
+        options: ParseOptions,
+        cache: Option<Arc<MetadataCache>>,
+        read: impl FnOnce() -> Result<LoraData> + Send + Sync + 'static,
+    ) -> Self {
+        let file = std::fs::metadata(&path).ok();
+        let modified = file.as_ref().and_then(|file| file.modified().ok());
+        let size = file.as_ref().map(|file| file.len());
+        // Only real files can be cached, since entries are checked against the file's metadata
+        let cache = cache.zip(modified.zip(size));
+        let key = path.clone();
+        MetadataRecord {
+            modified,
+            size,
+            preview: file.is_some().then(|| preview_image(&path)).flatten(),
+            path,
+            data: LazyLock::new(Box::new(move || {
+                if let Some((cache, (modified, size))) = &cache {
+                    if let Some(data) = cache.get(&key, *modified, *size, options) {
+                        return data;
+                    }
+                }
+                let data = Arc::new(read().unwrap_or_else(|e| LoraData {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                }));
+                // Failures aren't cached so they're retried next time
+                if let Some((cache, (modified, size))) = cache.filter(|_| data.error.is_none()) {
+                    cache.insert(key, modified, size, options, data.clone());
+                }
+                data
+            })),
+            loaded: AtomicBool::new(false),
+        }
+    }
+
+    /// Parse the record if it hasn't been already, blocking until the data is available
+    fn force(&self) -> &LoraData {
+        let data = LazyLock::force(&self.data);
+        self.loaded.store(true, Ordering::Release);
+        data
+    }
+
+    /// Get the parsed data, if the record has already been forced
+    fn get(&self) -> Option<&LoraData> {
+        self.loaded.load(Ordering::Acquire).then(|| &**self.data)
+    }
+
+    /// Copy a record which has already been loaded, sharing its parsed data
+    fn share(&self, preview: Option<PathBuf>) -> Option<MetadataRecord> {
+        self.get()?;
+        let data = Arc::clone(&self.data);
+        Some(MetadataRecord {
+            path: self.path.clone(),
+            modified: self.modified,
+            size: self.size,
+            preview,
+            data: LazyLock::new(Box::new(move || data)),
+            loaded: AtomicBool::new(true),
+        })
+    }
+}
+
+/// A store being forced by the background loader
+struct LoadJob {
+    store: MetadataStore,
+    /// Index of the next record for a worker to pick up in store order
+    next: AtomicUsize,
+    /// Index of a record to load before continuing in order, or `usize::MAX` for none
+    priority: AtomicUsize,
+    /// Whether a worker has taken each record, so none are loaded (or counted) twice
+    claimed: Vec<AtomicBool>,
+    /// Number of records which have finished loading
+    loaded: AtomicUsize,
+    /// Set when the user stops the scan, after which only prioritized records are loaded
+    cancelled: AtomicBool,
+    /// Set while background loading is off, so only prioritized records are loaded until
+    /// something needs the whole store, see [`Loader::load_all`]
+    on_demand: AtomicBool,
+}
+impl LoadJob {
+    /// Number of records loaded so far, and the total number in the store
+    ///
+    /// A paused job counts as finished, with only the records already loaded.
+    fn progress(&self) -> (usize, usize) {
+        let loaded = self.loaded.load(Ordering::Acquire);
+        if self.is_paused() {
+            (loaded, loaded)
+        } else {
+            (loaded, self.store.len())
+        }
+    }
+
+    /// Stop loading records in order, leaving those already loaded usable
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn is_on_demand(&self) -> bool {
+        self.on_demand.load(Ordering::Relaxed)
+    }
+
+    /// Whether only prioritized records are being loaded, because of a cancel or on-demand mode
+    fn is_paused(&self) -> bool {
+        self.is_cancelled() || self.is_on_demand()
+    }
+}
+
+/// Pool of worker threads which force the records of the current store in the background
+///
+/// Workers pick up records one at a time from whichever job is current, so replacing the job
+/// abandons the rest of the old store once the records already in progress have finished.
+struct Loader {
+    current: Mutex<Option<Arc<LoadJob>>>,
+    wake: Condvar,
+}
+impl Loader {
+    fn start(ctx: egui::Context) -> Arc<Loader> {
+        let loader = Arc::new(Loader {
+            current: Mutex::new(None),
+            wake: Condvar::new(),
+        });
+        // Loading is mostly I/O bound, so use a few threads even on small machines, but don't
+        // swamp a network share with too many requests at once
+        let threads = thread::available_parallelism().map_or(4, |n| n.get().clamp(2, 8));
+        for _ in 0..threads {
+            let loader = loader.clone();
+            let ctx = ctx.clone();
+            thread::spawn(move || loader.run(&ctx));
+        }
+        loader
+    }
+
+    /// Start loading a new store, beginning with the record at `priority`, and abandoning the
+    /// previous store
+    ///
+    /// With `on_demand` set, only prioritized records are loaded until [`Loader::load_all`].
+    fn load(&self, store: MetadataStore, priority: usize, on_demand: bool) -> Arc<LoadJob> {
+        // Records carried over from a refreshed store are already loaded, see [`refresh_records`]
+        let claimed: Vec<_> = store
+            .iter()
+            .map(|record| AtomicBool::new(record.get().is_some()))
+            .collect();
+        let loaded = claimed
+            .iter()
+            .filter(|claimed| claimed.load(Ordering::Relaxed))
+            .count();
+        let job = Arc::new(LoadJob {
+            claimed,
+            store,
+            next: AtomicUsize::new(0),
+            priority: AtomicUsize::new(priority),
+            loaded: AtomicUsize::new(loaded),
+            cancelled: AtomicBool::new(false),
+            on_demand: AtomicBool::new(on_demand),
+        });
+        *self.current.lock().unwrap() = Some(job.clone());
+        self.wake.notify_all();
+        job
+    }
+
+    /// Have the next free worker load the record at `index`, if it hasn't been already
+    ///
+    /// This wakes the workers, which will be idle if the job is paused.
+    fn prioritize(&self, job: &LoadJob, index: usize) {
+        let _current = self.current.lock().unwrap();
+        job.priority.store(index, Ordering::Relaxed);
+        self.wake.notify_all();
+    }
+
+    /// Load the rest of an on-demand job's store, for views which need every record
+    ///
+    /// A job the user cancelled stays cancelled.
+    fn load_all(&self, job: &LoadJob) {
+        let _current = self.current.lock().unwrap();
+        if job.on_demand.swap(false, Ordering::Relaxed) {
+            self.wake.notify_all();
+        }
+    }
+
+    fn run(&self, ctx: &egui::Context) {
+        loop {
+            let job = {
+                let mut current = self.current.lock().unwrap();
+                loop {
+                    match &*current {
+                        Some(job)
+                            if job.priority.load(Ordering::Relaxed) < job.store.len()
+                                || (!job.is_paused()
+                                    && job.next.load(Ordering::Relaxed) < job.store.len()) =>
+                        {
+                            break job.clone()
+                        }
+                        _ => current = self.wake.wait(current).unwrap(),
+                    }
+                }
+            };
+            let index = match job.priority.swap(usize::MAX, Ordering::Relaxed) {
+                index if index < job.store.len() => index,
+                _ if job.is_paused() => continue,
+                _ => job.next.fetch_add(1, Ordering::Relaxed),
+            };
+            let Some(claimed) = job.claimed.get(index) else {
+                continue;
+            };
+            if !claimed.swap(true, Ordering::AcqRel) {
+                job.store[index].force();
+                job.loaded.fetch_add(1, Ordering::Release);
+                ctx.request_repaint();
+            }
+        }
+    }
+}
+
+fn metadata_record(
+    path: &Path,
+    options: ParseOptions,
+    cache: &Arc<MetadataCache>,
+) -> MetadataRecord {
+    let file = path.to_path_buf();
+    MetadataRecord::new(
+        path.to_path_buf(),
+        options,
+        Some(cache.clone()),
+        move || {
+            if is_gguf(&file) {
+                let header = read_gguf_header(&file, options.header_limit)?;
+                Ok(LoraData::from_gguf(header, &options))
             } else {
-                Default::default()
+                let buffer = read_header(&file, options.header_limit)?;
+                LoraData::from_buffer(&buffer, &options)
             }
-        })),
+        },
     )
 }
 
+/// Create a record for a safetensors file inside a zip archive
+///
+/// The record's path is the entry name joined onto the archive path, which isn't a real file but
+/// gives a sensible name for display.
+fn zip_record(archive: &Path, entry: &str, options: ParseOptions) -> MetadataRecord {
+    let archive = archive.to_path_buf();
+    let entry = entry.to_string();
+    MetadataRecord::new(archive.join(&entry), options, None, move || {
+        let buffer = read_zip_header(&archive, &entry, options.header_limit)?;
+        LoraData::from_buffer(&buffer, &options)
+    })
+}
+
+/// Find an image with the same name as a model, such as `model.png` or `model.preview.png`
+fn preview_image(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let stem: &str = &stem;
+    ["", ".preview"]
+        .into_iter()
+        .flat_map(|suffix| {
+            ["png", "jpg", "jpeg", "webp"]
+                .into_iter()
+                .map(move |ext| path.with_file_name(format!("{stem}{suffix}.{ext}")))
+        })
+        .find(|image| image.is_file())
+}
+
+/// Whether a path has the extension of a model format we can read
+fn is_model(path: &Path) -> bool {
+    has_safetensors_extension(path) || is_gguf(path)
+}
+
+fn is_gguf(path: &Path) -> bool {
+    has_extension(path, "gguf")
+}
+
+/// List the model files in a directory, and optionally its subdirectories, sorted by path
+///
+/// Only the top directory failing to list is an error, unreadable subdirectories are skipped.
+/// Symlinked directories aren't followed, so a link pointing back up the tree can't loop.
+fn scan_directory(dir: &Path, recursive: bool) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![std::fs::read_dir(dir)?];
+    while let Some(entries) = pending.pop() {
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if recursive && entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                pending.extend(std::fs::read_dir(&path).ok());
+            } else if path.is_file() && is_model(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// How long the watched directory must be quiet before a change triggers a rescan
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Filesystem watcher on an open directory, noting when model files change
+///
+/// Events are debounced, so copying many files in at once only causes one rescan.
+struct DirectoryWatcher {
+    path: PathBuf,
+    recursive: bool,
+    _watcher: RecommendedWatcher,
+    /// Time of the latest change which hasn't been acted on yet
+    last_change: Arc<Mutex<Option<Instant>>>,
+}
+
+impl DirectoryWatcher {
+    fn new(path: &Path, recursive: bool, ctx: egui::Context) -> notify::Result<DirectoryWatcher> {
+        let last_change = Arc::new(Mutex::new(None));
+        let changes = last_change.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            let relevant = matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_)
+            ) && event.paths.iter().any(|path| is_model(path));
+            if relevant {
+                *changes.lock().unwrap() = Some(Instant::now());
+                ctx.request_repaint_after(WATCH_DEBOUNCE);
+            }
+        })?;
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(path, mode)?;
+        Ok(DirectoryWatcher {
+            path: path.to_path_buf(),
+            recursive,
+            _watcher: watcher,
+            last_change,
+        })
+    }
+
+    /// Whether the directory has changed and then been quiet for the debounce interval
+    fn changed(&self, ctx: &egui::Context) -> bool {
+        let mut last_change = self.last_change.lock().unwrap();
+        match *last_change {
+            Some(time) if time.elapsed() >= WATCH_DEBOUNCE => {
+                *last_change = None;
+                true
+            }
+            Some(time) => {
+                ctx.request_repaint_after(WATCH_DEBOUNCE - time.elapsed());
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Key for a folder in the saved sessions, so different spellings of a path share a session
+fn session_key(dir: &Path) -> PathBuf {
+    std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+fn is_archive(path: &Path) -> bool {
+    path.is_file() && has_extension(path, "zip")
+}
+
+/// Read the header for a record's path, which may point inside an archive (see [`zip_record`])
+fn read_record_header(path: &Path, limit: usize) -> Result<Vec<u8>> {
+    match path
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| is_archive(ancestor))
+    {
+        Some(archive) => {
+            // Zip entry names always use forward slashes, whatever the platform's separator is
+            let components: Vec<_> = path
+                .strip_prefix(archive)?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect();
+            read_zip_header(archive, &components.join("/"), limit)
+        }
+        None => read_header(path, limit),
+    }
+}
+
+/// Split a command line into words on whitespace, keeping anything in double quotes together
+///
+/// Backslashes aren't escapes, so Windows paths such as `"C:\Program Files\editor.exe"` can be
+/// quoted as they are.
+fn command_words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = None;
+    let mut quoted = false;
+    for c in command.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    words
+}
+
+/// Open a file with an editor command, or the system's default application if it's empty
+///
+/// The command is split by [`command_words`], so it can include arguments before the file name.
+fn open_in_editor(path: &Path, editor: &str) -> std::io::Result<()> {
+    let mut words = command_words(editor).into_iter();
+    let mut command = if let Some(program) = words.next() {
+        let mut command = Command::new(program);
+        command.args(words);
+        command
+    } else if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(path).spawn().map(|_| ())
+}
+
+/// Extract the JSON header from a buffer returned by [`read_header`], pretty-printed
+fn header_json(buffer: &[u8]) -> Result<String> {
+    let json: serde_json::Value = serde_json::from_slice(header_bytes(buffer)?)?;
+    Ok(serde_json::to_string_pretty(&json)?)
+}
+
+/// Pretty-print a metadata value if it's a JSON object or array
+fn pretty_json(value: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(value)
+        .ok()
+        .filter(|json| json.is_object() || json.is_array())
+        .and_then(|json| serde_json::to_string_pretty(&json).ok())
+}
+
+/// Serialize a metadata map as a pretty-printed JSON object with sorted keys
+///
+/// If `nested` is set, values which are themselves JSON objects or arrays are embedded as JSON
+/// rather than as strings.
+fn metadata_json(metadata: &HashMap<String, String>, nested: bool) -> String {
+    let object: serde_json::Map<String, serde_json::Value> = metadata
+        .iter()
+        .map(|(key, value)| {
+            let parsed = nested
+                .then(|| serde_json::from_str::<serde_json::Value>(value).ok())
+                .flatten()
+                .filter(|json| json.is_object() || json.is_array());
+            (
+                key.clone(),
+                parsed.unwrap_or_else(|| serde_json::Value::String(value.clone())),
+            )
+        })
+        .collect();
+    serde_json::to_string_pretty(&object).unwrap_or_default()
+}
+
+/// Write the header JSON of a model to a temporary file and open it in an editor
+fn open_header_in_editor(path: &Path, editor: &str, limit: usize) -> Result<()> {
+    let json = header_json(&read_record_header(path, limit)?)?;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file = std::env::temp_dir().join(format!("{stem}.header.json"));
+    std::fs::write(&file, json)?;
+    open_in_editor(&file, editor)?;
+    Ok(())
+}
+
+/// Build the records for a path, which can be a single model, an archive or a directory
+///
+/// This touches the filesystem (listing directories or reading the archive index), so is run off
+/// the UI thread. Returns None if the path doesn't exist or can't be read.
+fn build_records(
+    path: &Path,
+    options: ParseOptions,
+    recursive: bool,
+    cache: &Arc<MetadataCache>,
+) -> Option<Vec<MetadataRecord>> {
+    if is_archive(path) {
+        // Archives get one record per contained safetensors file
+        let entries = zip_entries(path).unwrap_or_default();
+        Some(
+            entries
+                .iter()
+                .map(|entry| zip_record(path, entry, options))
+                .collect(),
+        )
+    } else if path.is_file() {
+        // If the path is a single file, we just have one record
+        Some(vec![metadata_record(path, options, cache)])
+    } else if path.is_dir() {
+        // Otherwise scan the directory and add all safetensors files
+        scan_directory(path, recursive).ok().map(|files| {
+            files
+                .iter()
+                .map(|file| metadata_record(file, options, cache))
+                .collect()
+        })
+    } else {
+        None
+    }
+}
+
+/// Rebuild a directory's records from a fresh listing, reusing those which have already loaded
+///
+/// Files whose modification time and size haven't changed keep their parsed data, so only new and
+/// modified files have to be read again. Returns None if the listing is the same as before.
+fn refresh_records(
+    old: &[MetadataRecord],
+    files: &[PathBuf],
+    options: ParseOptions,
+    cache: &Arc<MetadataCache>,
+) -> Option<Vec<MetadataRecord>> {
+    let old: HashMap<&Path, &MetadataRecord> = old
+        .iter()
+        .map(|record| (record.path.as_path(), record))
+        .collect();
+    let mut changed = files.len() != old.len();
+    let records = files
+        .iter()
+        .map(|file| {
+            let stat = std::fs::metadata(file).ok();
+            let modified = stat.as_ref().and_then(|stat| stat.modified().ok());
+            let size = stat.map(|stat| stat.len());
+            let unchanged = old
+                .get(file.as_path())
+                .filter(|record| record.modified == modified && record.size == size);
+            changed |= unchanged.is_none();
+            unchanged
+                .and_then(|record| record.share(preview_image(file)))
+                .unwrap_or_else(|| metadata_record(file, options, cache))
+        })
+        .collect();
+    changed.then_some(records)
+}
+
 #[derive(Eq, PartialEq)]
 enum SearchResult {
     NoMatch,
@@ -44,6 +598,326 @@ enum SearchResult {
     Tag,
 }
 
+/// A side panel search, parsed once and then checked against every record
+enum SearchQuery {
+    /// Lowercased terms, which must all match the name or a tag
+    Terms(Vec<String>),
+    Regex(Regex),
+}
+impl SearchQuery {
+    fn new(text: &str, regex: bool) -> Result<SearchQuery, regex::Error> {
+        if regex {
+            RegexBuilder::new(text)
+                .case_insensitive(true)
+                .build()
+                .map(SearchQuery::Regex)
+        } else {
+            Ok(SearchQuery::Terms(
+                text.split_whitespace().map(str::to_lowercase).collect(),
+            ))
+        }
+    }
+
+    /// Check a record, which must already have been loaded
+    ///
+    /// A record is a name match if the file name matches on its own, otherwise a tag match if
+    /// the tags (along with the name, for multiple terms) match.
+    fn search(&self, record: &MetadataRecord) -> SearchResult {
+        let name = record
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let tags = record
+            .get()
+            .map_or(&[][..], |data| data.tag_frequencies.as_slice());
+        match self {
+            SearchQuery::Terms(terms) => {
+                let name = name.to_lowercase();
+                let tags: Vec<_> = tags.iter().map(|(tag, _)| tag.to_lowercase()).collect();
+                if terms.iter().all(|term| name.contains(term)) {
+                    SearchResult::Name
+                } else if terms
+                    .iter()
+                    .all(|term| name.contains(term) || tags.iter().any(|tag| tag.contains(term)))
+                {
+                    SearchResult::Tag
+                } else {
+                    SearchResult::NoMatch
+                }
+            }
+            SearchQuery::Regex(regex) => {
+                if regex.is_match(&name) {
+                    SearchResult::Name
+                } else if tags.iter().any(|(tag, _)| regex.is_match(tag)) {
+                    SearchResult::Tag
+                } else {
+                    SearchResult::NoMatch
+                }
+            }
+        }
+    }
+}
+
+const TOGGLE_SIDE_PANEL: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::B);
+const RESCAN: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F5);
+const ZOOM_IN: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Plus);
+const ZOOM_OUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Minus);
+const ZOOM_RESET: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Num0);
+
+/// Range and step of the UI zoom, as a multiple of the display's native scale
+const ZOOM_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0;
+const ZOOM_STEP: f32 = 1.1;
+
+/// Whether a record has one of the model types in `filter`, which allows everything if empty
+fn matches_type_filter(filter: &BTreeSet<ModelType>, record: &MetadataRecord) -> bool {
+    filter.is_empty()
+        || record
+            .get()
+            .is_some_and(|data| data.model_types.iter().any(|t| filter.contains(t)))
+}
+
+/// Orderings available for the side panel list
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) enum SidebarSort {
+    #[default]
+    Name,
+    Modified,
+    Size,
+}
+impl SidebarSort {
+    const ALL: [SidebarSort; 3] = [SidebarSort::Name, SidebarSort::Modified, SidebarSort::Size];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SidebarSort::Name => "Name",
+            SidebarSort::Modified => "Date modified",
+            SidebarSort::Size => "Size",
+        }
+    }
+
+    /// Compare two records, which doesn't need their metadata to be loaded
+    fn compare(&self, a: &MetadataRecord, b: &MetadataRecord) -> CmpOrdering {
+        match self {
+            SidebarSort::Name => a.path.file_name().cmp(&b.path.file_name()),
+            SidebarSort::Modified => a.modified.cmp(&b.modified),
+            SidebarSort::Size => a.size.cmp(&b.size),
+        }
+    }
+}
+
+/// Columns of the collection table, which can each be used as the sort key
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) enum CollectionColumn {
+    #[default]
+    Name,
+    Type,
+    Base,
+    Rank,
+    Alpha,
+    Scale,
+    Tags,
+    Size,
+}
+impl CollectionColumn {
+    const ALL: [CollectionColumn; 8] = [
+        CollectionColumn::Name,
+        CollectionColumn::Type,
+        CollectionColumn::Base,
+        CollectionColumn::Rank,
+        CollectionColumn::Alpha,
+        CollectionColumn::Scale,
+        CollectionColumn::Tags,
+        CollectionColumn::Size,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            CollectionColumn::Name => "Name",
+            CollectionColumn::Type => "Type",
+            CollectionColumn::Base => "Base",
+            CollectionColumn::Rank => "Rank",
+            CollectionColumn::Alpha => "Alpha",
+            CollectionColumn::Scale => "Scale",
+            CollectionColumn::Tags => "Tags",
+            CollectionColumn::Size => "Size",
+        }
+    }
+
+    fn compare(&self, a: &MetadataRecord, b: &MetadataRecord) -> CmpOrdering {
+        let (data_a, data_b) = (&*a.data, &*b.data);
+        match self {
+            CollectionColumn::Name => a.path.file_name().cmp(&b.path.file_name()),
+            CollectionColumn::Type => model_types_text(data_a).cmp(&model_types_text(data_b)),
+            CollectionColumn::Base => data_a.base_model.cmp(&data_b.base_model),
+            CollectionColumn::Rank => data_a.network_dim.cmp(&data_b.network_dim),
+            CollectionColumn::Alpha => data_a
+                .network_alpha
+                .partial_cmp(&data_b.network_alpha)
+                .unwrap_or(CmpOrdering::Equal),
+            CollectionColumn::Scale => network_scale(data_a)
+                .partial_cmp(&network_scale(data_b))
+                .unwrap_or(CmpOrdering::Equal),
+            CollectionColumn::Tags => data_a
+                .tag_frequencies
+                .len()
+                .cmp(&data_b.tag_frequencies.len()),
+            CollectionColumn::Size => data_a.file_size.cmp(&data_b.file_size),
+        }
+    }
+}
+
+fn model_types_text(data: &LoraData) -> String {
+    model_type_names(data).join(", ")
+}
+
+/// Names of a model's types, followed by "rank-stabilized" for rsLoRA
+fn model_type_names(data: &LoraData) -> Vec<String> {
+    let mut types: Vec<_> = data.model_types.iter().map(|t| t.to_string()).collect();
+    if data.rank_stabilized() {
+        types.push("rank-stabilized".to_string());
+    }
+    types
+}
+
+/// Rank to display for a model, preferring the range measured from the tensors
+fn rank_text(data: &LoraData) -> Option<String> {
+    match (data.rank_range(), data.network_dim) {
+        (Some((min, max)), _) if min != max => Some(format!("{min}\u{2013}{max}")),
+        (Some((rank, _)), _) => Some(rank.to_string()),
+        (None, Some(dim)) => Some(dim.to_string()),
+        (None, None) => None,
+    }
+}
+
+/// Scale factor the LoRA is applied with at weight 1.0, from the alpha and rank in the metadata
+fn network_scale(data: &LoraData) -> Option<f64> {
+    Some(data.network_alpha? / rank_divisor(data, data.network_dim? as usize))
+}
+
+/// What alpha is divided by to get the scale at some rank, which is the square root for rsLoRA
+fn rank_divisor(data: &LoraData, rank: usize) -> f64 {
+    if data.rank_stabilized() {
+        (rank as f64).sqrt()
+    } else {
+        rank as f64
+    }
+}
+
+/// Tensor count, broken down by the part of the base model the tensors apply to
+fn structure_text(data: &LoraData) -> String {
+    let mut groups: BTreeMap<String, usize> = BTreeMap::new();
+    for (network, count) in data.tensors_by_network() {
+        let group = if network.is_text_encoder() {
+            "Text Encoder".to_string()
+        } else {
+            network.to_string()
+        };
+        *groups.entry(group).or_default() += count;
+    }
+    let mut text = format!("Tensors: {}", data.tensors.len());
+    if !groups.is_empty() {
+        let mut groups: Vec<_> = groups.into_iter().collect();
+        groups.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        let groups: Vec<_> = groups
+            .iter()
+            .map(|(group, count)| format!("{group} {count}"))
+            .collect();
+        text.push_str(&format!(" ({})", groups.join(", ")));
+    }
+    text
+}
+
+/// Effective scale as displayed, a range when the rank varies between layers
+fn scale_text(data: &LoraData) -> Option<String> {
+    let alpha = data.network_alpha?;
+    match data.rank_range() {
+        Some((min, max)) if min != max => Some(format!(
+            "{:.3}\u{2013}{:.3}",
+            alpha / rank_divisor(data, max),
+            alpha / rank_divisor(data, min)
+        )),
+        Some((rank, _)) if rank > 0 => Some(format!("{:.3}", alpha / rank_divisor(data, rank))),
+        _ => network_scale(data).map(|scale| format!("{scale:.3}")),
+    }
+}
+
+/// How long ago a file was modified, roughly, such as "3 days ago"
+fn format_age(modified: SystemTime) -> String {
+    let Ok(age) = modified.elapsed() else {
+        return "just now".to_string();
+    };
+    let (count, unit) = match age.as_secs() {
+        seconds if seconds < 60 => return "just now".to_string(),
+        seconds if seconds < 3600 => (seconds / 60, "minute"),
+        seconds if seconds < 86400 => (seconds / 3600, "hour"),
+        seconds if seconds < 60 * 86400 => (seconds / 86400, "day"),
+        seconds if seconds < 730 * 86400 => (seconds / (30 * 86400), "month"),
+        seconds => (seconds / (365 * 86400), "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+/// Rough human-readable duration, for time estimates
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    if seconds < 60 {
+        format!("{seconds}s left")
+    } else if seconds < 3600 {
+        format!("{}m {}s left", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h {}m left", seconds / 3600, seconds / 60 % 60)
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in ["KB", "MB", "GB", "TB"] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    if unit == "B" {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {unit}")
+    }
+}
+
+/// Format a count to three significant figures with a K/M/B suffix
+fn format_count(count: u64) -> String {
+    let mut value = count as f64;
+    let mut unit = "";
+    for next in ["K", "M", "B"] {
+        if value < 1000.0 {
+            break;
+        }
+        value /= 1000.0;
+        unit = next;
+    }
+    if unit.is_empty() {
+        format!("{count}")
+    } else {
+        let decimals = if value >= 100.0 {
+            0
+        } else if value >= 10.0 {
+            1
+        } else {
+            2
+        };
+        format!("{value:.decimals$}{unit}")
+    }
+}
+
 struct Analysis {
     results: Vec<(String, f32, f32, u32)>,
 }
@@ -109,315 +983,2534 @@ impl Analysis {
     }
 }
 
-#[derive(Default, Serialize, Deserialize)]
-pub struct App {
-    lora_file: Option<PathBuf>,
-    selected: usize,
-    #[serde(skip)]
-    open_dialog: Option<FileDialog>,
-    #[serde(skip)]
-    metadata: Option<MetadataStore>,
-    metadata_dialog: bool,
-    tensors_dialog: bool,
-    #[serde(skip)]
-    background_loader: Option<Sender<MetadataStore>>,
-    #[serde(skip)]
-    loader_state: Arc<Mutex<(usize, usize)>>,
-    search_text: String,
-    #[serde(skip)]
-    search_results: Option<Vec<SearchResult>>,
-    #[serde(skip)]
-    analysis: Option<Analysis>,
+/// Block a tensor belongs to, used to group the tensor list
+///
+/// This is the name up to and including the first numeric component, such as
+/// `lora_unet_down_blocks_0` or `model.diffusion_model.input_blocks.1`, or the module name before
+/// the first dot if there is no number.
+fn tensor_block(name: &str) -> &str {
+    let mut start = 0;
+    for (end, c) in name.char_indices().chain([(name.len(), '.')]) {
+        if c == '.' || c == '_' {
+            if end > start && name[start..end].bytes().all(|b| b.is_ascii_digit()) {
+                return &name[..end];
+            }
+            start = end + 1;
+        }
+    }
+    name.split('.').next().unwrap_or(name)
 }
 
-impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>, path: Option<String>) -> App {
-        let mut app = if let Some(path) = path {
-            App {
-                lora_file: PathBuf::from_str(&path).ok(),
-                ..Default::default()
-            }
-        } else if let Some(storage) = cc.storage {
-            eframe::get_value(storage, eframe::APP_KEY).unwrap_or_default()
-        } else {
-            Default::default()
-        };
-        let (send, recv) = channel();
-        app.background_loader = Some(send);
-
-        let ctx = cc.egui_ctx.clone();
-        let state = app.loader_state.clone();
-        thread::spawn(move || loop {
-            let mut store = recv.recv().unwrap();
-            let mut i = 0;
-            while i < store.len() {
-                LazyLock::force(&store[i].1);
-                i += 1;
-                *state.lock().unwrap() = (i, store.len());
-                ctx.request_repaint();
-                match recv.try_recv() {
-                    Ok(new_store) => {
-                        store = new_store;
-                        i = 0;
-                    }
-                    Err(std::sync::mpsc::TryRecvError::Disconnected) => panic!(),
-                    Err(_) => (),
-                }
+/// Rows of the tensor list, grouped by block and formatted once when the dialog opens
+///
+/// Models can have thousands of tensors, so this saves regrouping and formatting all of them
+/// every frame. The rows are rebuilt when the filter changes.
+struct TensorRows {
+    /// Filter the rows were built with, in lowercase
+    filter: String,
+    /// Block name, and the name, shape, dtype and parameter count of each tensor in it
+    blocks: Vec<(String, Vec<[String; 4]>)>,
+}
+impl TensorRows {
+    fn new(data: &LoraData, filter: String) -> TensorRows {
+        let mut blocks: BTreeMap<&str, Vec<[String; 4]>> = BTreeMap::new();
+        for (name, shape, dtype) in &data.tensors {
+            if !filter.is_empty() && !name.to_lowercase().contains(&filter) {
+                continue;
             }
-        });
-        app
+            let count = shape.iter().product::<usize>();
+            let shape: Vec<_> = shape.iter().map(|v| format!("{v}")).collect();
+            blocks.entry(tensor_block(name)).or_default().push([
+                name.clone(),
+                shape.join(", "),
+                dtype.clone(),
+                format_count(count as u64),
+            ]);
+        }
+        TensorRows {
+            filter,
+            blocks: blocks
+                .into_iter()
+                .map(|(block, rows)| (block.to_string(), rows))
+                .collect(),
+        }
     }
 }
 
-impl eframe::App for App {
-    fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        eframe::set_value(storage, eframe::APP_KEY, self);
+/// How widely a tag is used across a collection of models, see [`tag_usage`]
+#[derive(Clone, Debug, PartialEq)]
+struct TagUsage {
+    tag: String,
+    /// Number of models trained with the tag
+    models: usize,
+    /// Frequency of the tag summed over all the models
+    images: f64,
+}
+
+/// Count how many models use each tag, and how often altogether
+///
+/// The result is sorted by descending model count, then by descending frequency.
+fn tag_usage<'a>(models: impl IntoIterator<Item = &'a LoraData>) -> Vec<TagUsage> {
+    let mut usage: HashMap<&str, (usize, f64)> = HashMap::new();
+    for data in models {
+        for (tag, count) in &data.tag_frequencies {
+            let entry = usage.entry(tag.as_str()).or_default();
+            entry.0 += 1;
+            entry.1 += count;
+        }
     }
+    let mut usage: Vec<_> = usage
+        .into_iter()
+        .map(|(tag, (models, images))| TagUsage {
+            tag: tag.to_string(),
+            models,
+            images,
+        })
+        .collect();
+    usage.sort_by(|a, b| {
+        b.models
+            .cmp(&a.models)
+            .then(b.images.total_cmp(&a.images))
+            .then_with(|| a.tag.cmp(&b.tag))
+    });
+    usage
+}
 
-    fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
-        // Menu bar
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            egui::menu::bar(ui, |ui| {
-                ui.menu_button("File", |ui| {
-                    if ui.button("Open model").clicked() {
-                        let filter = Box::new({
-                            let ext = Some(OsStr::new("safetensors"));
-                            move |path: &Path| -> bool { path.extension() == ext }
-                        });
-                        let mut dialog =
-                            FileDialog::open_file(self.lora_file.as_ref().and_then(|path| {
-                                if path.is_file() {
-                                    path.parent().map(|p| p.to_path_buf())
-                                } else {
-                                    Some(path.to_path_buf())
-                                }
-                            }))
-                            .show_files_filter(filter);
-                        dialog.open();
-                        self.open_dialog = Some(dialog);
-                        ui.close_menu();
-                    }
-                    if ui.button("Scan directory").clicked() {
-                        let filter = Box::new(|path: &Path| -> bool { path.is_dir() });
-                        let mut dialog =
-                            FileDialog::select_folder(self.lora_file.as_ref().and_then(|path| {
-                                if path.is_file() {
-                                    path.parent().map(|p| p.to_path_buf())
-                                } else {
-                                    Some(path.to_path_buf())
-                                }
-                            }))
-                            .show_files_filter(filter);
+/// Column the tag usage table is sorted by
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum TagUsageColumn {
+    Tag,
+    #[default]
+    Models,
+    Images,
+}
+impl TagUsageColumn {
+    const ALL: [TagUsageColumn; 3] = [
+        TagUsageColumn::Tag,
+        TagUsageColumn::Models,
+        TagUsageColumn::Images,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            TagUsageColumn::Tag => "Tag",
+            TagUsageColumn::Models => "Models",
+            TagUsageColumn::Images => "Images",
+        }
+    }
+
+    fn compare(&self, a: &TagUsage, b: &TagUsage) -> CmpOrdering {
+        match self {
+            TagUsageColumn::Tag => a.tag.cmp(&b.tag),
+            TagUsageColumn::Models => a.models.cmp(&b.models),
+            TagUsageColumn::Images => a.images.total_cmp(&b.images),
+        }
+    }
+}
+
+/// Colour scheme for the whole app
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Theme {
+    /// Match the operating system's light or dark mode
+    #[default]
+    System,
+    Dark,
+    Light,
+}
+impl Theme {
+    /// Visuals for the theme, given the system's theme if it's known
+    fn visuals(&self, system: Option<eframe::Theme>) -> Option<egui::Visuals> {
+        match self {
+            Theme::System => system.map(|theme| theme.egui_visuals()),
+            Theme::Dark => Some(egui::Visuals::dark()),
+            Theme::Light => Some(egui::Visuals::light()),
+        }
+    }
+}
+
+/// Line format used when exporting a tag list
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+enum TagExportFormat {
+    /// `tag, frequency` on each line
+    #[default]
+    WithFrequency,
+    /// Just the tag on each line
+    TagsOnly,
+}
+
+/// Columns of the tag grid, which can each be used as the sort key
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+enum TagColumn {
+    Tag,
+    #[default]
+    Frequency,
+}
+impl TagColumn {
+    const ALL: [TagColumn; 2] = [TagColumn::Tag, TagColumn::Frequency];
+
+    fn label(&self) -> &'static str {
+        match self {
+            TagColumn::Tag => "Tag",
+            TagColumn::Frequency => "Frequency",
+        }
+    }
+
+    fn compare(&self, a: &(String, f64), b: &(String, f64)) -> CmpOrdering {
+        match self {
+            TagColumn::Tag => a.0.cmp(&b.0),
+            TagColumn::Frequency => a.1.total_cmp(&b.1),
+        }
+    }
+}
+
+/// Format a tag list for export, one tag per line in the order given
+fn format_tags(tags: &[(String, f64)], format: TagExportFormat) -> String {
+    tags.iter()
+        .map(|(tag, freq)| match format {
+            TagExportFormat::WithFrequency => format!("{tag}, {freq}\n"),
+            TagExportFormat::TagsOnly => format!("{tag}\n"),
+        })
+        .collect()
+}
+
+/// Number of tags shown in the tag frequency chart
+const TAG_CHART_SIZE: usize = 30;
+
+/// Draw a horizontal bar filled to `fraction` of the available width, with a label on top
+fn frequency_bar(ui: &mut egui::Ui, fraction: f32, text: impl ToString) -> egui::Response {
+    let height = ui.spacing().interact_size.y;
+    let (rect, response) = ui.allocate_exact_size(
+        egui::vec2(ui.available_width(), height),
+        egui::Sense::hover(),
+    );
+    let visuals = ui.visuals();
+    ui.painter()
+        .rect_filled(rect, 2.0, visuals.extreme_bg_color);
+    let mut fill = rect;
+    fill.set_width(rect.width() * fraction.clamp(0.0, 1.0));
+    ui.painter()
+        .rect_filled(fill, 2.0, visuals.selection.bg_fill);
+    ui.painter().text(
+        rect.left_center() + egui::vec2(4.0, 0.0),
+        egui::Align2::LEFT_CENTER,
+        text.to_string(),
+        egui::TextStyle::Body.resolve(ui.style()),
+        visuals.text_color(),
+    );
+    response
+}
+
+/// Show two models side by side, returning true if the comparison should be closed
+///
+/// The tags are split into those unique to each model and those they share, each kept in order of
+/// frequency.
+fn comparison_ui(
+    ui: &mut egui::Ui,
+    left: (&MetadataRecord, &LoraData),
+    right: (&MetadataRecord, &LoraData),
+) -> bool {
+    let mut close = false;
+    ui.horizontal(|ui| {
+        ui.strong("Comparing models");
+        close = ui.button("Clear comparison").clicked();
+    });
+    let name = |record: &MetadataRecord| {
+        record
+            .path
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned()
+    };
+    let unknown = || "\u{2014}".to_string();
+    egui::Grid::new("comparison")
+        .num_columns(3)
+        .striped(true)
+        .show(ui, |ui| {
+            let rows: [(&str, fn(&LoraData) -> Option<String>); 4] = [
+                ("Model type", |data: &LoraData| {
+                    Some(model_types_text(data)).filter(|t| !t.is_empty())
+                }),
+                ("Base checkpoint", |data: &LoraData| data.base_model.clone()),
+                ("Rank", rank_text),
+                ("Alpha", |data: &LoraData| {
+                    data.network_alpha.map(|alpha| alpha.to_string())
+                }),
+            ];
+            ui.label("");
+            ui.strong(name(left.0));
+            ui.strong(name(right.0));
+            ui.end_row();
+            for (label, value) in rows {
+                ui.label(label);
+                ui.label(value(left.1).unwrap_or_else(unknown));
+                ui.label(value(right.1).unwrap_or_else(unknown));
+                ui.end_row();
+            }
+        });
+    ui.separator();
+
+    let tag_set = |data: &LoraData| -> BTreeSet<String> {
+        data.tag_frequencies
+            .iter()
+            .map(|(tag, _)| tag.clone())
+            .collect()
+    };
+    let (left_tags, right_tags) = (tag_set(left.1), tag_set(right.1));
+    let unique = |data: &LoraData, other: &BTreeSet<String>| -> Vec<String> {
+        data.tag_frequencies
+            .iter()
+            .filter(|(tag, _)| !other.contains(tag))
+            .map(|(tag, _)| tag.clone())
+            .collect()
+    };
+    let columns = [
+        (
+            format!("Only in {}", name(left.0)),
+            unique(left.1, &right_tags),
+        ),
+        (
+            "Shared".to_string(),
+            left.1
+                .tag_frequencies
+                .iter()
+                .filter(|(tag, _)| right_tags.contains(tag))
+                .map(|(tag, _)| tag.clone())
+                .collect(),
+        ),
+        (
+            format!("Only in {}", name(right.0)),
+            unique(right.1, &left_tags),
+        ),
+    ];
+    ui.columns(3, |uis| {
+        for (ui, (heading, tags)) in uis.iter_mut().zip(columns) {
+            ui.strong(format!("{heading} ({})", tags.len()));
+            egui::ScrollArea::vertical()
+                .id_source(&heading)
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    for tag in tags {
+                        ui.label(tag);
+                    }
+                });
+        }
+    });
+    close
+}
+
+/// A hash of a model's tensor data, such as a check against the hash stored in its metadata
+///
+/// The file is hashed on its own thread, since it has to be read in full.
+struct HashJob {
+    size: u64,
+    hashed: Arc<AtomicU64>,
+    result: Receiver<Result<String, String>>,
+    /// The hash once it's been calculated, or why it couldn't be
+    outcome: Option<Result<String, String>>,
+}
+
+impl HashJob {
+    /// Hash the file at `path` with `hash`, which reports progress out of `size` bytes
+    fn start(
+        path: &Path,
+        size: u64,
+        hash: fn(&Path, &AtomicU64) -> Result<String>,
+        ctx: egui::Context,
+    ) -> HashJob {
+        let hashed = Arc::new(AtomicU64::new(0));
+        let (send, result) = channel();
+        let (path, progress) = (path.to_path_buf(), hashed.clone());
+        thread::spawn(move || {
+            send.send(hash(&path, &progress).map_err(|e| e.to_string()))
+                .ok();
+            ctx.request_repaint();
+        });
+        HashJob {
+            size,
+            hashed,
+            result,
+            outcome: None,
+        }
+    }
+
+    /// Show how far through the file the hash is
+    fn progress_bar(&self, ui: &mut egui::Ui) {
+        let hashed = self.hashed.load(Ordering::Relaxed);
+        ui.add(
+            egui::ProgressBar::new(hashed as f32 / self.size.max(1) as f32)
+                .desired_width(200.0)
+                .show_percentage(),
+        );
+        ui.ctx().request_repaint_after(Duration::from_millis(100));
+    }
+
+    /// Pick up the result if the hash has finished
+    fn poll(&mut self) {
+        if self.outcome.is_none() {
+            self.outcome = match self.result.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(Err("Hashing stopped".to_string())),
+            };
+        }
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Fingerprint of a record's tensors, see [`content_fingerprint`], if it's a readable file
+///
+/// Models inside archives aren't fingerprinted, since they'd have to be decompressed in full.
+fn record_fingerprint(record: &MetadataRecord) -> Option<String> {
+    record.size?;
+    content_fingerprint(&record.path, &AtomicU64::new(0)).ok()
+}
+
+/// Summary of a model for reports, which both the CSV and JSON Lines output are written from
+#[derive(Serialize)]
+struct Summary<'a> {
+    path: &'a Path,
+    model_types: Vec<String>,
+    base_model: Option<&'a str>,
+    rank: Option<String>,
+    alpha: Option<f64>,
+    parameters: u64,
+    file_size: u64,
+    /// The five most frequent tags
+    top_tags: Vec<&'a str>,
+    /// Only present if fingerprints were asked for, and null if the model couldn't be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<Option<String>>,
+}
+
+impl<'a> Summary<'a> {
+    const CSV_HEADER: [&'static str; 8] = [
+        "filename",
+        "model types",
+        "base model",
+        "rank",
+        "alpha",
+        "parameters",
+        "file size",
+        "top tags",
+    ];
+
+    /// Summarise a record, reading the whole file for its fingerprint if `fingerprint` is set
+    fn new(record: &'a MetadataRecord, data: &'a LoraData, fingerprint: bool) -> Self {
+        Summary {
+            path: &record.path,
+            model_types: model_type_names(data),
+            base_model: data.base_model.as_deref(),
+            rank: rank_text(data),
+            alpha: data.network_alpha,
+            parameters: data.parameter_count(),
+            file_size: record.size.unwrap_or(data.file_size),
+            top_tags: data
+                .tag_frequencies
+                .iter()
+                .take(5)
+                .map(|(tag, _)| tag.as_str())
+                .collect(),
+            fingerprint: fingerprint.then(|| record_fingerprint(record)),
+        }
+    }
+
+    /// The fields of a CSV row, in the order of [`Summary::CSV_HEADER`] then the fingerprint
+    fn csv_fields(&self) -> Vec<String> {
+        let mut fields = vec![
+            self.path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            self.model_types.join(", "),
+            self.base_model.unwrap_or_default().to_string(),
+            self.rank.clone().unwrap_or_default(),
+            self.alpha
+                .map(|alpha| alpha.to_string())
+                .unwrap_or_default(),
+            self.parameters.to_string(),
+            self.file_size.to_string(),
+            self.top_tags.join(", "),
+        ];
+        if let Some(fingerprint) = &self.fingerprint {
+            fields.push(fingerprint.clone().unwrap_or_default());
+        }
+        fields
+    }
+}
+
+/// Write a CSV summary with one row per record, waiting for any which haven't been loaded yet
+///
+/// With `fingerprints` set, every file is read in full for a fingerprint column, which is slow
+/// for large models.
+fn write_report(
+    records: &[MetadataRecord],
+    fingerprints: bool,
+    out: impl Write,
+) -> std::io::Result<()> {
+    let mut out = BufWriter::new(out);
+    let mut header = Summary::CSV_HEADER.to_vec();
+    if fingerprints {
+        header.push("fingerprint");
+    }
+    writeln!(out, "{}", header.join(","))?;
+    for record in records {
+        let summary = Summary::new(record, record.force(), fingerprints);
+        let fields: Vec<_> = summary
+            .csv_fields()
+            .iter()
+            .map(|field| csv_field(field))
+            .collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    out.flush()
+}
+
+/// Write a CSV report for the models at some paths to `out`, for use from the command line
+pub(crate) fn export_report(
+    paths: &[PathBuf],
+    out: &Path,
+    options: ParseOptions,
+    fingerprints: bool,
+) -> Result<()> {
+    let cache = Arc::default();
+    let mut records = Vec::new();
+    for path in paths {
+        records.extend(
+            build_records(path, options, false, &cache)
+                .ok_or(anyhow!("Couldn't read {}", path.display()))?,
+        );
+    }
+    write_report(&records, fingerprints, std::fs::File::create(out)?)?;
+    Ok(())
+}
+
+/// Read paths from `input`, one per line, and write a JSON object for each model to `out`
+///
+/// Each line is flushed as soon as it's written, so the output can be consumed as it arrives.
+/// Files which fail to load get an object with just the path and the error.
+fn write_jsonl(
+    input: impl BufRead,
+    mut out: impl Write,
+    options: ParseOptions,
+    fingerprints: bool,
+) -> Result<()> {
+    let cache = Arc::default();
+    for line in input.lines() {
+        let line = line?;
+        let path = Path::new(line.trim());
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let Some(records) = build_records(path, options, false, &cache) else {
+            let error = format!("Couldn't read {}", path.display());
+            writeln!(out, "{}", serde_json::json!({"path": path, "error": error}))?;
+            out.flush()?;
+            continue;
+        };
+        for record in &records {
+            let data = record.force();
+            let value = match &data.error {
+                Some(error) => serde_json::json!({"path": record.path, "error": error}),
+                None => serde_json::json!(Summary::new(record, data, fingerprints)),
+            };
+            writeln!(out, "{value}")?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Summarise the models at paths read from stdin as JSON Lines on stdout, for use in scripts
+pub(crate) fn export_jsonl(options: ParseOptions, fingerprints: bool) -> Result<()> {
+    write_jsonl(
+        std::io::stdin().lock(),
+        std::io::stdout().lock(),
+        options,
+        fingerprints,
+    )
+}
+
+/// Absolute weight statistics for every tensor in a model
+struct WeightStats {
+    /// Name, minimum, maximum and mean absolute value of each tensor
+    tensors: Vec<(String, f32, f32, f32)>,
+    /// Minimum, maximum and mean absolute value over all tensors
+    overall: (f32, f32, f32),
+}
+impl WeightStats {
+    /// Calculate statistics for a model, which needs the whole file to be read
+    fn new(path: &Path) -> Result<WeightStats> {
+        let tensors = candle_core::safetensors::load(path, &Device::Cpu)?;
+        let mut names: Vec<_> = tensors.keys().collect();
+        names.sort();
+
+        let mut stats = Vec::new();
+        let (mut min, mut max, mut sum, mut count) = (f32::INFINITY, 0.0f32, 0.0f64, 0usize);
+        for name in names {
+            // Skip anything we can't convert, and empty tensors which have no statistics
+            let Some(abs) = tensors[name]
+                .to_dtype(candle_core::DType::F32)
+                .and_then(|t| t.abs()?.flatten_all())
+                .ok()
+                .filter(|t| t.elem_count() > 0)
+            else {
+                continue;
+            };
+            let tensor_min = abs.min(0)?.to_scalar::<f32>()?;
+            let tensor_max = abs.max(0)?.to_scalar::<f32>()?;
+            let tensor_mean = abs.mean_all()?.to_scalar::<f32>()?;
+
+            min = min.min(tensor_min);
+            max = max.max(tensor_max);
+            sum += f64::from(tensor_mean) * abs.elem_count() as f64;
+            count += abs.elem_count();
+            stats.push((name.clone(), tensor_min, tensor_max, tensor_mean));
+        }
+        if count == 0 {
+            return Err(anyhow!("No tensors with numeric data"));
+        }
+
+        Ok(WeightStats {
+            tensors: stats,
+            overall: (min, max, (sum / count as f64) as f32),
+        })
+    }
+
+    /// Calculate statistics for a model on its own thread, since the whole file is read
+    fn start(path: &Path, ctx: egui::Context) -> Receiver<Result<WeightStats, String>> {
+        let (send, result) = channel();
+        let path = path.to_path_buf();
+        thread::spawn(move || {
+            send.send(WeightStats::new(&path).map_err(|e| e.to_string()))
+                .ok();
+            ctx.request_repaint();
+        });
+        result
+    }
+}
+
+/// Number of paths kept in the File > Recent menu
+const RECENT_LIMIT: usize = 10;
+
+/// Number of folders whose selection and search are remembered
+const SESSION_LIMIT: usize = 100;
+
+/// Where the user was in a folder, restored when it's opened again
+#[derive(Serialize, Deserialize)]
+struct SessionState {
+    selected: Option<PathBuf>,
+    search_text: String,
+    /// When the folder was last left, so the least recently used are forgotten first
+    last_used: SystemTime,
+}
+
+/// A saved switch which is on by default, so [`App`] can derive `Default`
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+struct DefaultOn(bool);
+impl Default for DefaultOn {
+    fn default() -> Self {
+        DefaultOn(true)
+    }
+}
+
+/// A saved factor or threshold which is 1 by default, such as the zoom
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+struct DefaultOne<T>(T);
+impl<T: From<u8>> Default for DefaultOne<T> {
+    fn default() -> Self {
+        DefaultOne(T::from(1))
+    }
+}
+
+/// Number of the most frequent tags included by "Copy tags", 20 by default
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+struct CopyTagCount(usize);
+impl Default for CopyTagCount {
+    fn default() -> Self {
+        CopyTagCount(20)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct App {
+    lora_file: Option<PathBuf>,
+    /// Paths opened together as one store, used when `lora_file` is None
+    file_set: Vec<PathBuf>,
+    /// Recently opened paths, most recent first
+    recent: Vec<PathBuf>,
+    /// Selection and search for recently opened folders, keyed by canonical path
+    sessions: HashMap<PathBuf, SessionState>,
+    selected: usize,
+    #[serde(skip)]
+    open_dialog: Option<FileDialog>,
+    /// Save dialog for exporting tags, along with the tags to write
+    #[serde(skip)]
+    export_dialog: Option<(FileDialog, Vec<(String, f64)>)>,
+    /// Save dialog for a CSV report, along with the store to report on
+    #[serde(skip)]
+    report_dialog: Option<(FileDialog, MetadataStore)>,
+    /// CSV report being written on its own thread
+    #[serde(skip)]
+    pending_report: Option<Receiver<std::io::Result<()>>>,
+    /// Whether reports include a fingerprint of each model, which means reading every file in full
+    report_fingerprints: bool,
+    tag_export_format: TagExportFormat,
+    #[serde(skip)]
+    metadata: Option<MetadataStore>,
+    metadata_dialog: bool,
+    tensors_dialog: bool,
+    #[serde(skip)]
+    tensor_filter: String,
+    #[serde(skip)]
+    tensor_rows: Option<TensorRows>,
+    /// Whether developer diagnostics are shown, from the `--debug` flag
+    #[serde(skip)]
+    debug: bool,
+    #[serde(skip)]
+    tensor_types_dialog: bool,
+    /// Type inferred from each tensor for the tensor types dialog, worked out when it opens
+    #[serde(skip)]
+    tensor_types: Option<Vec<(String, Option<ModelType>)>>,
+    errors_dialog: bool,
+    collection_dialog: bool,
+    collection_sort: CollectionColumn,
+    collection_descending: bool,
+    tag_usage_dialog: bool,
+    /// Tags of every model in the store, counted when the dialog is first shown after a scan
+    #[serde(skip)]
+    tag_usage: Option<Vec<TagUsage>>,
+    #[serde(skip)]
+    tag_usage_filter: String,
+    #[serde(skip)]
+    tag_usage_sort: TagUsageColumn,
+    #[serde(skip)]
+    tag_usage_descending: DefaultOn,
+    #[serde(skip)]
+    background_loader: Option<Arc<Loader>>,
+    /// Progress of the background loader through the current store
+    #[serde(skip)]
+    load_job: Option<Arc<LoadJob>>,
+    /// When the background loader started on the current store, for estimating time remaining
+    #[serde(skip)]
+    scan_started: Option<Instant>,
+    search_text: String,
+    #[serde(skip)]
+    search_results: Option<Vec<SearchResult>>,
+    #[serde(skip)]
+    analysis: Option<Analysis>,
+    /// Weight statistics being calculated for the selected model
+    #[serde(skip)]
+    pending_weight_stats: Option<Receiver<Result<WeightStats, String>>>,
+    /// Weight statistics shown in their own window, with the name of the model they're for
+    #[serde(skip)]
+    weight_stats: Option<(String, WeightStats)>,
+    #[serde(skip)]
+    fingerprint: Option<HashJob>,
+    /// Pretty-printed metadata values of the selected model, by key, formatted when first expanded
+    #[serde(skip)]
+    pretty_metadata: HashMap<String, Option<String>>,
+    /// Hash in the metadata, and the check of the tensor data against it
+    #[serde(skip)]
+    hash_check: Option<(String, HashJob)>,
+    /// Move the selection to the first match whenever the search results change
+    auto_select_match: bool,
+    hide_side_panel: bool,
+    /// Seconds between checks of the open directory for new or removed files, 0 to disable
+    refresh_interval: u32,
+    /// Rescan the open directory when models are added or removed
+    watch_directory: DefaultOn,
+    /// Include models in subfolders of the open folder
+    recursive_scan: bool,
+    /// Load every model in the store in the background, rather than only when selected
+    background_loading: DefaultOn,
+    #[serde(skip)]
+    settings_dialog: bool,
+    #[serde(skip)]
+    watcher: Option<DirectoryWatcher>,
+    /// Listing of the open directory being compared with the store, see [`App::refresh`]
+    #[serde(skip)]
+    pending_refresh: Option<Receiver<Option<Vec<MetadataRecord>>>>,
+    /// Parsed models from previous sessions, shared with the records which use it
+    #[serde(skip)]
+    cache: Arc<MetadataCache>,
+    #[serde(skip)]
+    last_scan: Option<Instant>,
+    parse_options: ParseOptions,
+    /// Folder opened on startup when no path is given on the command line
+    default_folder: Option<PathBuf>,
+    /// Command used to open files externally, empty for the system default
+    editor: String,
+    /// Message shown at the top of the main panel until dismissed
+    #[serde(skip)]
+    notice: Option<String>,
+    /// Records being built for the current path, if a scan is in progress
+    #[serde(skip)]
+    pending_scan: Option<Receiver<Option<Vec<MetadataRecord>>>>,
+    /// Path of the model to select once the store has been rebuilt
+    #[serde(skip)]
+    reselect: Option<PathBuf>,
+    /// Number of the most frequent tags included by "Copy tags"
+    copy_tag_count: CopyTagCount,
+    /// Show the most frequent tags as a bar chart instead of the full grid
+    tag_chart: bool,
+    /// Tags less frequent than this are hidden from the tag list
+    min_tag_frequency: DefaultOne<f64>,
+    tag_sort: TagColumn,
+    /// Copy JSON metadata values as nested JSON rather than strings
+    nest_metadata_json: DefaultOn,
+    tag_descending: DefaultOn,
+    /// Dataset directory whose tags are shown, or None for the merged tags
+    #[serde(skip)]
+    tag_dir: Option<String>,
+    sidebar_sort: SidebarSort,
+    sidebar_descending: bool,
+    /// Second model pinned with ctrl-click to compare against the selected one
+    #[serde(skip)]
+    compare_selected: Option<usize>,
+    /// Treat the search text as a regular expression rather than a list of terms
+    search_regex: bool,
+    #[serde(skip)]
+    search_invalid: bool,
+    /// Model types shown in the side panel, or all of them if empty
+    #[serde(skip)]
+    type_filter: BTreeSet<ModelType>,
+    /// Store indices in the order they're listed in the side panel
+    #[serde(skip)]
+    display_order: Vec<usize>,
+    theme: Theme,
+    /// Scale of the UI relative to the display's native scale
+    zoom: DefaultOne<f32>,
+}
+
+impl App {
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        paths: Vec<PathBuf>,
+        config: Config,
+        config_error: Option<String>,
+        debug: bool,
+    ) -> App {
+        // Always restore the saved state so preferences survive, even if we're starting with a
+        // path given on the command line
+        let mut app: App = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default();
+        app.apply_config(config);
+        app.notice = config_error;
+        // Forget recent paths that have gone since last time, once rather than every frame the
+        // menu is shown
+        app.recent.retain(|path| path.exists());
+        app.debug = debug;
+        if let Some(path) = MetadataCache::default_path().filter(|path| path.exists()) {
+            match MetadataCache::load(&path) {
+                Ok(cache) => app.cache = Arc::new(cache),
+                Err(e) => app.notice = Some(format!("Couldn't read the metadata cache: {e}")),
+            }
+        }
+        // Paths on the command line take priority over the default folder, and either replaces
+        // whatever was open last time. Several paths are opened together as one store.
+        let mut paths = paths;
+        if paths.is_empty() {
+            paths.extend(app.default_folder.clone());
+        }
+        if !paths.is_empty() {
+            app.search_text.clear();
+        }
+        match paths.len() {
+            0 => (),
+            1 => app.open(paths.remove(0)),
+            _ => app.open_files(paths),
+        }
+        // Zoom is handled by our own shortcuts, so it can be saved with the rest of the state
+        cc.egui_ctx
+            .options_mut(|options| options.zoom_with_keyboard = false);
+        app.set_zoom(&cc.egui_ctx, app.zoom.0);
+        egui_extras::install_image_loaders(&cc.egui_ctx);
+        app.background_loader = Some(Loader::start(cc.egui_ctx.clone()));
+        app
+    }
+
+    /// Override saved preferences with any set in the config file
+    fn apply_config(&mut self, config: Config) {
+        if let Some(auto_select_match) = config.auto_select_match {
+            self.auto_select_match = auto_select_match;
+        }
+        if let Some(refresh_interval) = config.refresh_interval {
+            self.refresh_interval = refresh_interval;
+        }
+        if let Some(watch_directory) = config.watch_directory {
+            self.watch_directory.0 = watch_directory;
+        }
+        if let Some(fold_tag_case) = config.fold_tag_case {
+            self.parse_options.fold_tag_case = fold_tag_case;
+        }
+        if let Some(normalize_tags) = config.normalize_tags {
+            self.parse_options.normalize_tags = normalize_tags;
+        }
+        if let Some(header_limit) = config.header_limit_bytes() {
+            self.parse_options.header_limit = header_limit;
+        }
+        if let Some(hide_side_panel) = config.hide_side_panel {
+            self.hide_side_panel = hide_side_panel;
+        }
+        if let Some(sort) = config.sort {
+            self.collection_sort = sort;
+        }
+        if let Some(descending) = config.sort_descending {
+            self.collection_descending = descending;
+        }
+        if let Some(sort) = config.list_sort {
+            self.sidebar_sort = sort;
+        }
+        if let Some(descending) = config.list_descending {
+            self.sidebar_descending = descending;
+        }
+        if let Some(editor) = config.editor {
+            self.editor = editor;
+        }
+        if let Some(theme) = config.theme {
+            self.theme = theme;
+        }
+    }
+
+    /// Open a model, archive or directory, replacing the current store
+    fn open(&mut self, path: PathBuf) {
+        self.remember_session();
+        self.recent.retain(|recent| *recent != path);
+        self.recent.insert(0, path.clone());
+        self.recent.truncate(RECENT_LIMIT);
+        let session = path
+            .is_dir()
+            .then(|| self.sessions.get(&session_key(&path)))
+            .flatten();
+        if let Some(session) = session {
+            self.search_text = session.search_text.clone();
+        }
+        let reselect = session.and_then(|session| session.selected.clone());
+        self.lora_file = Some(path);
+        self.file_set.clear();
+        self.close_store();
+        self.reselect = reselect;
+    }
+
+    /// Scale the whole UI, within [`ZOOM_RANGE`]
+    fn set_zoom(&mut self, ctx: &egui::Context, zoom: f32) {
+        self.zoom.0 = zoom.clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end());
+        ctx.set_zoom_factor(self.zoom.0);
+    }
+
+    /// Record the selection and search in the open folder, to restore when it's reopened
+    fn remember_session(&mut self) {
+        let Some(dir) = self.lora_file.as_ref().filter(|path| path.is_dir()) else {
+            return;
+        };
+        let Some(metadata) = &self.metadata else {
+            return;
+        };
+        let session = SessionState {
+            selected: metadata
+                .get(self.selected)
+                .map(|record| record.path.clone()),
+            search_text: self.search_text.clone(),
+            last_used: SystemTime::now(),
+        };
+        self.sessions.insert(session_key(dir), session);
+        while self.sessions.len() > SESSION_LIMIT {
+            let oldest = self
+                .sessions
+                .iter()
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(path, _)| path.clone());
+            if let Some(oldest) = oldest {
+                self.sessions.remove(&oldest);
+            }
+        }
+    }
+
+    /// Open several paths together as a single store
+    fn open_files(&mut self, files: Vec<PathBuf>) {
+        self.remember_session();
+        self.lora_file = None;
+        self.file_set = files;
+        self.close_store();
+    }
+
+    /// Drop the store and anything still being built or searched from it, so it's rebuilt
+    fn drop_store(&mut self) {
+        self.metadata = None;
+        self.pending_scan = None;
+        self.pending_refresh = None;
+        self.search_results = None;
+        // Stop the workers too, rather than leaving them loading a store nobody will look at
+        if let Some(job) = self.load_job.take() {
+            job.cancel();
+        }
+    }
+
+    /// Drop the current store and any per-model state, so the store is rebuilt for a new path
+    fn close_store(&mut self) {
+        self.drop_store();
+        self.selected = 0;
+        self.metadata_dialog = false;
+        self.tensors_dialog = false;
+        self.tensor_types_dialog = false;
+        self.errors_dialog = false;
+        self.fingerprint = None;
+        self.pretty_metadata.clear();
+        self.hash_check = None;
+        self.pending_weight_stats = None;
+        self.weight_stats = None;
+    }
+
+    /// Replace the current store, queueing it for background loading
+    fn set_store(&mut self, records: Vec<MetadataRecord>) {
+        let metadata = Arc::new(records);
+        if let Some(path) = self.reselect.take() {
+            self.selected = metadata.iter().position(|r| r.path == path).unwrap_or(0);
+        }
+        self.load_job = self
+            .background_loader
+            .as_ref()
+            .map(|loader| loader.load(metadata.clone(), self.selected, !self.background_loading.0));
+        self.scan_started = Some(Instant::now());
+        self.metadata = Some(metadata);
+        self.search_results = None;
+        self.tag_usage = None;
+        self.compare_selected = None;
+        self.last_scan = Some(Instant::now());
+        self.update_display_order();
+    }
+
+    /// Recompute the side panel order after the store or sort settings change
+    ///
+    /// This only reorders the list, so `selected` still refers to the same record.
+    fn update_display_order(&mut self) {
+        let Some(metadata) = &self.metadata else {
+            self.display_order.clear();
+            return;
+        };
+        let mut order: Vec<_> = (0..metadata.len()).collect();
+        order.sort_by(|&a, &b| {
+            let ordering = self.sidebar_sort.compare(&metadata[a], &metadata[b]);
+            if self.sidebar_descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.display_order = order;
+    }
+
+    /// Rebuild the store for the current path, keeping the selected model if it still exists
+    fn rescan(&mut self) {
+        self.reselect = self
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get(self.selected))
+            .map(|record| record.path.clone());
+        self.drop_store();
+    }
+
+    /// Check the open directory for added, removed or modified models, in the background
+    ///
+    /// Unlike [`App::rescan`], models which haven't changed keep their loaded data, and the store
+    /// is only replaced if the listing differs.
+    fn refresh(&mut self, ctx: &egui::Context) {
+        if self.pending_refresh.is_some() || self.pending_scan.is_some() {
+            return;
+        }
+        let Some((dir, metadata)) = self
+            .lora_file
+            .clone()
+            .filter(|path| path.is_dir())
+            .zip(self.metadata.clone())
+        else {
+            return;
+        };
+        let (options, recursive, cache) =
+            (self.parse_options, self.recursive_scan, self.cache.clone());
+        let (send, recv) = channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let records = scan_directory(&dir, recursive)
+                .ok()
+                .and_then(|files| refresh_records(&metadata, &files, options, &cache));
+            send.send(records).ok();
+            ctx.request_repaint();
+        });
+        self.pending_refresh = Some(recv);
+    }
+
+    /// Load the record at `index` in the current store ahead of the rest
+    fn prioritize(&self, index: usize) {
+        if let (Some(loader), Some(job)) = (&self.background_loader, &self.load_job) {
+            loader.prioritize(job, index);
+        }
+    }
+
+    /// Load every record of the store, for searches and views which cover the whole store
+    ///
+    /// Without background loading, records are only loaded when selected until this is called.
+    fn load_all(&self) {
+        if let (Some(loader), Some(job)) = (&self.background_loader, &self.load_job) {
+            loader.load_all(job);
+        }
+    }
+
+    /// Select the record at `index` in the current store, closing any per-model windows
+    fn select(&mut self, index: usize) {
+        self.selected = index;
+        self.prioritize(index);
+        self.metadata_dialog = false;
+        self.tensors_dialog = false;
+        self.tensor_types_dialog = false;
+        self.tensor_filter.clear();
+        self.tag_dir = None;
+        self.fingerprint = None;
+        self.pretty_metadata.clear();
+        self.hash_check = None;
+        self.pending_weight_stats = None;
+        self.weight_stats = None;
+    }
+}
+
+impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.remember_session();
+        eframe::set_value(storage, eframe::APP_KEY, self);
+        if let Some(path) = MetadataCache::default_path() {
+            if let Err(e) = self.cache.save(&path) {
+                self.notice = Some(format!("Couldn't save the metadata cache: {e}"));
+            }
+        }
+    }
+
+    fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
+        // Checked every frame, so following the system picks up changes to its theme
+        if let Some(visuals) = self.theme.visuals(frame.info().system_theme) {
+            if ctx.style().visuals.dark_mode != visuals.dark_mode {
+                ctx.set_visuals(visuals);
+            }
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&TOGGLE_SIDE_PANEL)) {
+            self.hide_side_panel = !self.hide_side_panel;
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&RESCAN)) {
+            self.rescan();
+        }
+        // Ctrl and = is accepted for zooming in too, since + needs shift on most keyboards
+        if ctx.input_mut(|i| {
+            i.consume_shortcut(&ZOOM_IN)
+                || i.consume_key(egui::Modifiers::COMMAND, egui::Key::Equals)
+        }) {
+            self.set_zoom(ctx, self.zoom.0 * ZOOM_STEP);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&ZOOM_OUT)) {
+            self.set_zoom(ctx, self.zoom.0 / ZOOM_STEP);
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&ZOOM_RESET)) {
+            self.set_zoom(ctx, 1.0);
+        }
+
+        // Menu bar
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Open model").clicked() {
+                        let filter = Box::new({
+                            move |path: &Path| -> bool {
+                                is_model(path) || has_extension(path, "zip")
+                            }
+                        });
+                        let mut dialog =
+                            FileDialog::open_file(self.lora_file.as_ref().and_then(|path| {
+                                if path.is_file() {
+                                    path.parent().map(|p| p.to_path_buf())
+                                } else {
+                                    Some(path.to_path_buf())
+                                }
+                            }))
+                            .show_files_filter(filter)
+                            .multi_select(true);
+                        dialog.open();
+                        self.open_dialog = Some(dialog);
+                        ui.close_menu();
+                    }
+                    if ui.button("Scan directory").clicked() {
+                        let filter = Box::new(|path: &Path| -> bool { path.is_dir() });
+                        let mut dialog =
+                            FileDialog::select_folder(self.lora_file.as_ref().and_then(|path| {
+                                if path.is_file() {
+                                    path.parent().map(|p| p.to_path_buf())
+                                } else {
+                                    Some(path.to_path_buf())
+                                }
+                            }))
+                            .show_files_filter(filter);
                         dialog.open();
                         self.open_dialog = Some(dialog);
                         ui.close_menu();
                     }
+                    if ui
+                        .add_enabled(
+                            self.lora_file.is_some() || !self.file_set.is_empty(),
+                            egui::Button::new("Rescan").shortcut_text(ctx.format_shortcut(&RESCAN)),
+                        )
+                        .on_hover_text("Reload the list and every model in it")
+                        .clicked()
+                    {
+                        self.rescan();
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Recent", |ui| {
+                        if self.recent.is_empty() {
+                            ui.label("Nothing opened yet");
+                        }
+                        let mut chosen = None;
+                        for path in &self.recent {
+                            if ui.button(path.display().to_string()).clicked() {
+                                chosen = Some(path.clone());
+                                ui.close_menu();
+                            }
+                        }
+                        if let Some(path) = chosen {
+                            if path.exists() {
+                                self.open(path);
+                            } else {
+                                self.notice = Some(format!("{} no longer exists", path.display()));
+                                self.recent.retain(|recent| *recent != path);
+                            }
+                        }
+                    });
+                    // Only offer a report once every record has loaded, so writing it won't block
+                    let (loaded, total) =
+                        self.load_job.as_ref().map_or((0, 0), |job| job.progress());
+                    let paused = self.load_job.as_ref().is_some_and(|job| job.is_paused());
+                    if ui
+                        .add_enabled(
+                            total > 0 && loaded == total && !paused,
+                            egui::Button::new("Export CSV report\u{2026}"),
+                        )
+                        .on_hover_text("Save a summary of every model in the list")
+                        .on_disabled_hover_text("Every model in the list has to load first")
+                        .clicked()
+                    {
+                        if let Some(metadata) = &self.metadata {
+                            let folder = self.lora_file.as_ref().and_then(|path| {
+                                if path.is_dir() {
+                                    Some(path.to_path_buf())
+                                } else {
+                                    path.parent().map(Path::to_path_buf)
+                                }
+                            });
+                            let mut dialog =
+                                FileDialog::save_file(folder).default_filename("report.csv");
+                            dialog.open();
+                            self.report_dialog = Some((dialog, metadata.clone()));
+                        }
+                        ui.close_menu();
+                    }
+                    let on_demand = self
+                        .load_job
+                        .as_ref()
+                        .is_some_and(|job| job.is_on_demand() && !job.is_cancelled());
+                    if on_demand
+                        && ui
+                            .button("Load all models")
+                            .on_hover_text("Load every model in the list, e.g. for a report")
+                            .clicked()
+                    {
+                        self.load_all();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    let current_dir = self.lora_file.clone().filter(|path| path.is_dir());
+                    if ui
+                        .add_enabled(
+                            current_dir.is_some(),
+                            egui::Button::new("Set as default folder"),
+                        )
+                        .on_hover_text("Open this folder on startup")
+                        .clicked()
+                    {
+                        self.default_folder = current_dir;
+                        ui.close_menu();
+                    }
+                    let clear = ui.add_enabled(
+                        self.default_folder.is_some(),
+                        egui::Button::new("Clear default folder"),
+                    );
+                    let clear = match &self.default_folder {
+                        Some(folder) => clear.on_hover_text(folder.display().to_string()),
+                        None => clear,
+                    };
+                    if clear.clicked() {
+                        self.default_folder = None;
+                        ui.close_menu();
+                    }
+                    ui.separator();
                     if ui.button("Quit").clicked() {
                         ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         ui.close_menu();
                     }
                 });
+                ui.menu_button("View", |ui| {
+                    let label = if self.hide_side_panel {
+                        "Show side panel"
+                    } else {
+                        "Hide side panel"
+                    };
+                    if ui
+                        .add(
+                            egui::Button::new(label)
+                                .shortcut_text(ctx.format_shortcut(&TOGGLE_SIDE_PANEL)),
+                        )
+                        .clicked()
+                    {
+                        self.hide_side_panel = !self.hide_side_panel;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.metadata.is_some(),
+                            egui::Button::new("Collection table"),
+                        )
+                        .clicked()
+                    {
+                        self.collection_dialog = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(self.metadata.is_some(), egui::Button::new("All tags"))
+                        .on_hover_text("Every tag in the folder, with how many models use it")
+                        .clicked()
+                    {
+                        self.tag_usage_dialog = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    for (label, shortcut, zoom) in [
+                        ("Zoom in", ZOOM_IN, self.zoom.0 * ZOOM_STEP),
+                        ("Zoom out", ZOOM_OUT, self.zoom.0 / ZOOM_STEP),
+                        ("Reset zoom", ZOOM_RESET, 1.0),
+                    ] {
+                        if ui
+                            .add(
+                                egui::Button::new(label)
+                                    .shortcut_text(ctx.format_shortcut(&shortcut)),
+                            )
+                            .clicked()
+                        {
+                            self.set_zoom(ctx, zoom);
+                        }
+                    }
+                });
+                if ui.button("Settings").clicked() {
+                    self.settings_dialog = true;
+                }
                 ui.add_space(16.0);
             });
 
             // Set path and clear metadata if open dialog has finished
             if let Some(dialog) = &mut self.open_dialog {
                 if dialog.show(ctx).selected() {
-                    if let Some(path) = dialog.path() {
-                        self.lora_file = Some(path.to_path_buf());
-                        self.metadata = None;
-                        self.search_results = None;
-                        *self.loader_state.lock().unwrap() = (0, 0);
-                        self.selected = 0;
-                        self.metadata_dialog = false;
-                        self.tensors_dialog = false;
+                    // Selecting several models opens them together, like a folder of their own
+                    let selection: Vec<_> = dialog
+                        .selection()
+                        .into_iter()
+                        .map(Path::to_path_buf)
+                        .collect();
+                    if selection.len() > 1 {
+                        self.open_files(selection);
+                    } else if let Some(path) = dialog.path().map(Path::to_path_buf) {
+                        self.open(path);
                     }
                 }
             }
         });
 
-        // Populate the metadata record if it's empty and we have a path defined
-        if self.metadata.is_none() {
-            if let Some(lora) = &self.lora_file {
-                if lora.is_file() {
-                    // If the path is a single file, we just have one record
-                    let metadata = Arc::new(vec![metadata_record(lora)]);
-                    if let Some(loader) = &self.background_loader {
-                        loader.send(metadata.clone()).ok();
-                    }
-                    self.metadata = Some(metadata);
-                } else if lora.is_dir() {
-                    // Otherwise scan the directory and add all safetensors files
-                    if let Ok(files) = std::fs::read_dir(lora) {
-                        let ext = Some(OsStr::new("safetensors"));
-                        let metadata = Arc::new({
-                            let mut files: Vec<_> = files
-                                .into_iter()
-                                .filter_map(|f| {
-                                    f.ok().and_then(|f| {
-                                        let path = f.path();
-                                        if path.is_file() && path.extension() == ext {
-                                            Some(metadata_record(&path))
-                                        } else {
-                                            None
-                                        }
-                                    })
-                                })
-                                .collect();
-                            files.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-                            files
-                        });
-                        if let Some(loader) = &self.background_loader {
-                            loader.send(metadata.clone()).ok();
-                        }
-                        self.metadata = Some(metadata);
+        // Open models and folders dropped onto the window, with several making a store of their own
+        let dropped: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        if !dropped.is_empty() {
+            let (mut paths, ignored): (Vec<_>, Vec<_>) = dropped
+                .into_iter()
+                .partition(|path| path.is_dir() || is_archive(path) || is_model(path));
+            if !ignored.is_empty() {
+                self.notice = Some(format!(
+                    "Ignored {} dropped item(s) which aren't models or folders",
+                    ignored.len()
+                ));
+            }
+            match paths.len() {
+                0 => (),
+                1 => self.open(paths.pop().unwrap()),
+                _ => self.open_files(paths),
+            }
+        }
+
+        // Watch the open directory so new models show up without waiting for a refresh
+        let watch_path = self
+            .lora_file
+            .as_ref()
+            .filter(|path| self.watch_directory.0 && path.is_dir());
+        match watch_path {
+            Some(path)
+                if self.watcher.as_ref().map_or(true, |w| {
+                    &w.path != path || w.recursive != self.recursive_scan
+                }) =>
+            {
+                match DirectoryWatcher::new(path, self.recursive_scan, ctx.clone()) {
+                    Ok(watcher) => self.watcher = Some(watcher),
+                    Err(e) => {
+                        self.notice = Some(format!("Couldn't watch the folder for changes: {e}"));
+                        self.watch_directory.0 = false;
+                        self.watcher = None;
+                    }
+                }
+            }
+            Some(_) => (),
+            None => self.watcher = None,
+        }
+        // A change seen while a refresh is running is picked up once it finishes
+        if self.pending_refresh.is_none()
+            && self
+                .watcher
+                .as_ref()
+                .is_some_and(|watcher| watcher.changed(ctx))
+        {
+            self.refresh(ctx);
+        }
+
+        // Periodically check whether the directory listing has changed, and merge it in if it has
+        if self.refresh_interval > 0 {
+            let interval = Duration::from_secs(self.refresh_interval.into());
+            let is_dir = self.lora_file.as_ref().is_some_and(|path| path.is_dir());
+            if let Some(last_scan) = self.last_scan.filter(|_| is_dir && self.metadata.is_some()) {
+                let elapsed = last_scan.elapsed();
+                if elapsed >= interval {
+                    self.refresh(ctx);
+                    self.last_scan = Some(Instant::now());
+                    ctx.request_repaint_after(interval);
+                } else {
+                    ctx.request_repaint_after(interval - elapsed);
+                }
+            }
+        }
+        if let Some(refresh) = &self.pending_refresh {
+            match refresh.try_recv() {
+                Ok(records) => {
+                    self.pending_refresh = None;
+                    if let Some(records) = records {
+                        self.reselect = self
+                            .metadata
+                            .as_ref()
+                            .and_then(|m| m.get(self.selected))
+                            .map(|record| record.path.clone());
+                        self.set_store(records);
                     }
                 }
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => self.pending_refresh = None,
+            }
+        }
+
+        // Populate the metadata record if it's empty and we have a path defined. Building the
+        // records lists directories, which can be slow, so is done on a separate thread.
+        if self.metadata.is_none()
+            && self.pending_scan.is_none()
+            && (self.lora_file.is_some() || !self.file_set.is_empty())
+        {
+            let lora = self.lora_file.clone();
+            let files = self.file_set.clone();
+            let options = self.parse_options;
+            let recursive = self.recursive_scan;
+            let cache = self.cache.clone();
+            let (send, recv) = channel();
+            let ctx = ctx.clone();
+            thread::spawn(move || {
+                let records = match lora {
+                    Some(lora) => build_records(&lora, options, recursive, &cache),
+                    // A set of files is combined into one store, skipping any which can't be read
+                    None => Some(
+                        files
+                            .iter()
+                            .flat_map(|file| {
+                                build_records(file, options, recursive, &cache).unwrap_or_default()
+                            })
+                            .collect(),
+                    ),
+                };
+                send.send(records).ok();
+                ctx.request_repaint();
+            });
+            self.pending_scan = Some(recv);
+        }
+        if let Some(scan) = &self.pending_scan {
+            match scan.try_recv() {
+                Ok(records) => {
+                    self.pending_scan = None;
+                    // An unreadable path gets an empty store, so we don't keep rescanning it
+                    self.set_store(records.unwrap_or_default());
+                }
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => self.pending_scan = None,
             }
         }
+        // Searches and views of the whole store need every record, even when loading on demand
+        if !self.search_text.is_empty() || self.tag_usage_dialog || self.collection_dialog {
+            self.load_all();
+        }
 
-        // If our path is to a directory, add a side panel to select LoRAs
-        if let Some(path) = &self.lora_file {
-            if path.is_dir() {
-                egui::SidePanel::left("left_panel").show(ctx, |ui| {
-                    let (loaded, total) = *self.loader_state.lock().unwrap();
-                    if loaded < total {
-                        ui.label(format!("Scanning {loaded} / {total}"));
+        // Status bar along the bottom, which has room for the full path the side panel leaves out
+        if let Some(metadata) = self
+            .metadata
+            .as_ref()
+            .filter(|metadata| !metadata.is_empty())
+        {
+            let (loaded, total) = self.load_job.as_ref().map_or((0, 0), |job| job.progress());
+            let cancelled = self.load_job.as_ref().is_some_and(|job| job.is_cancelled());
+            let on_demand = self.load_job.as_ref().is_some_and(|job| job.is_on_demand());
+            egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(match metadata.len() {
+                        1 => "1 model".to_string(),
+                        count => format!("{count} models"),
+                    });
+                    if cancelled {
                         ui.separator();
-                    } else if ui
-                        .add(TextEdit::singleline(&mut self.search_text))
-                        .changed()
-                    {
-                        self.search_results = None;
+                        ui.label(format!(
+                            "Loading cancelled, {loaded} of {} loaded",
+                            metadata.len()
+                        ));
+                    } else if on_demand {
+                        ui.separator();
+                        ui.label(format!("{loaded} of {} loaded (on demand)", metadata.len()))
+                            .on_hover_text("Models are loaded when selected or searched");
+                    } else if loaded < total {
+                        ui.separator();
+                        ui.label(format!("Loading {loaded} / {total}"));
+                    }
+                    if self.pending_report.is_some() {
+                        ui.separator();
+                        ui.spinner();
+                        ui.label("Writing report\u{2026}");
+                    }
+                    // Failed files, reachable here even when the side panel is hidden
+                    if loaded == total {
+                        let failed = metadata
+                            .iter()
+                            .filter(|record| record.get().is_some_and(|data| data.error.is_some()))
+                            .count();
+                        if failed > 0 {
+                            ui.separator();
+                            if ui
+                                .link(format!("\u{26a0} {failed} failed"))
+                                .on_hover_text("Show the files which failed to load")
+                                .clicked()
+                            {
+                                self.errors_dialog = true;
+                            }
+                        }
+                    }
+                    if let Some(record) = metadata.get(self.selected) {
+                        ui.separator();
+                        ui.add(
+                            egui::Label::new(record.path.display().to_string())
+                                .selectable(true)
+                                .truncate(),
+                        )
+                        .on_hover_text(record.path.display().to_string());
+                    }
+                });
+            });
+        }
+
+        // If our path is to a directory or archive, or several files were opened, add a side panel
+        // to select LoRAs
+        let mut clicked = None;
+        let mut pinned = None;
+        let mut notice = None;
+        let mut resort = false;
+        let show_list = match &self.lora_file {
+            Some(path) => path.is_dir() || is_archive(path),
+            None => !self.file_set.is_empty(),
+        };
+        if !self.hide_side_panel && show_list {
+            egui::SidePanel::left("left_panel").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let sort = self.sidebar_sort;
+                    egui::ComboBox::from_id_source("sidebar_sort")
+                        .selected_text(self.sidebar_sort.label())
+                        .show_ui(ui, |ui| {
+                            for option in SidebarSort::ALL {
+                                ui.selectable_value(&mut self.sidebar_sort, option, option.label());
+                            }
+                        });
+                    // Dates are usually wanted newest first, to find the latest download
+                    if sort != self.sidebar_sort && self.sidebar_sort == SidebarSort::Modified {
+                        self.sidebar_descending = true;
+                    }
+                    let arrow = if self.sidebar_descending {
+                        "\u{2b07}"
+                    } else {
+                        "\u{2b06}"
+                    };
+                    let direction = match (self.sidebar_sort, self.sidebar_descending) {
+                        (SidebarSort::Modified, true) => "Newest first",
+                        (SidebarSort::Modified, false) => "Oldest first",
+                        (_, true) => "Descending",
+                        (_, false) => "Ascending",
+                    };
+                    if ui.small_button(arrow).on_hover_text(direction).clicked() {
+                        self.sidebar_descending = !self.sidebar_descending;
+                        resort = true;
+                    }
+                    resort |= sort != self.sidebar_sort;
+                });
+                // Offer the types present in the store, plus any already selected so they
+                // can be cleared after switching folders
+                if let Some(metadata) = &self.metadata {
+                    let mut types = self.type_filter.clone();
+                    types.extend(
+                        metadata
+                            .iter()
+                            .filter_map(|record| record.get())
+                            .flat_map(|data| data.model_types.iter().copied()),
+                    );
+                    if !types.is_empty() {
+                        egui::CollapsingHeader::new("Filter by type").show(ui, |ui| {
+                            for model_type in types {
+                                let mut checked = self.type_filter.contains(&model_type);
+                                if ui.checkbox(&mut checked, model_type.to_string()).changed() {
+                                    if checked {
+                                        self.type_filter.insert(model_type);
+                                    } else {
+                                        self.type_filter.remove(&model_type);
+                                    }
+                                }
+                            }
+                        });
                     }
+                }
 
-                    if let Some(metadata) = &self.metadata {
-                        if loaded < total || total == 0 {
+                let (loaded, total) = self.load_job.as_ref().map_or((0, 0), |job| job.progress());
+                if self.pending_scan.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Scanning directory\u{2026}");
+                    });
+                    ui.separator();
+                } else if loaded < total {
+                    let mut text = format!("{loaded} / {total}");
+                    // Estimate from the average time per model so far
+                    if let Some(started) = self.scan_started.filter(|_| loaded > 0) {
+                        let remaining = started.elapsed().as_secs_f64() / loaded as f64
+                            * (total - loaded) as f64;
+                        text.push_str(&format!(" \u{2014} about {}", format_duration(remaining)));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button("Cancel")
+                            .on_hover_text("Stop loading, keeping the models loaded so far")
+                            .clicked()
+                        {
+                            if let Some(job) = &self.load_job {
+                                job.cancel();
+                            }
+                        }
+                        ui.add(
+                            egui::ProgressBar::new(loaded as f32 / total as f32)
+                                .text(text)
+                                .animate(true),
+                        );
+                    });
+                }
+                // The search box stays up while loading, and results appear once loading finishes
+                if self.pending_scan.is_none() {
+                    ui.horizontal(|ui| {
+                        let regex = ui
+                            .checkbox(&mut self.search_regex, ".*")
+                            .on_hover_text("Search with a regular expression");
+                        let mut search = TextEdit::singleline(&mut self.search_text)
+                            .hint_text("Search names and tags");
+                        if self.search_invalid {
+                            search = search.text_color(Color32::RED);
+                        }
+                        if ui.add(search).changed() || regex.changed() {
                             self.search_results = None;
-                        } else if self.search_results.is_none() {
-                            self.search_results = Some(
-                                metadata
-                                    .iter()
-                                    .map(|model| {
-                                        let name_match = model
-                                            .0
-                                            .file_name()
-                                            .and_then(|s| {
-                                                s.to_str().map(|s| {
-                                                    s.to_ascii_lowercase().contains(
-                                                        &self.search_text.to_ascii_lowercase(),
-                                                    )
-                                                })
-                                            })
-                                            .unwrap_or(false);
-                                        let tag_match =
-                                            model.1.tag_frequencies.iter().any(|(tag, _)| {
-                                                tag.to_ascii_lowercase().contains(
-                                                    &self.search_text.to_ascii_lowercase(),
-                                                )
-                                            });
-                                        if name_match {
-                                            SearchResult::Name
-                                        } else if tag_match {
-                                            SearchResult::Tag
-                                        } else {
-                                            SearchResult::NoMatch
+                        }
+                    });
+                }
+
+                if let Some(metadata) = &self.metadata {
+                    if loaded < total || total == 0 {
+                        self.search_results = None;
+                    } else if self.search_results.is_none() {
+                        // An invalid regex matches nothing, and the search box turns red
+                        let query = SearchQuery::new(&self.search_text, self.search_regex);
+                        self.search_invalid = query.is_err();
+                        self.search_results = Some(
+                            metadata
+                                .iter()
+                                .map(|model| match &query {
+                                    Ok(query) => query.search(model),
+                                    Err(_) => SearchResult::NoMatch,
+                                })
+                                .collect(),
+                        );
+                        if self.auto_select_match && !self.search_text.is_empty() {
+                            clicked = self.search_results.as_ref().and_then(|results| {
+                                self.display_order.iter().copied().find(|&i| {
+                                    results[i] != SearchResult::NoMatch
+                                        && matches_type_filter(&self.type_filter, &metadata[i])
+                                })
+                            });
+                        }
+                    }
+                }
+
+                // Once the scan is done, link to a summary of any files that failed to load
+                if let Some(metadata) = &self.metadata {
+                    let failed = if loaded < total || total == 0 {
+                        0
+                    } else {
+                        metadata
+                            .iter()
+                            .filter(|record| record.get().is_some_and(|data| data.error.is_some()))
+                            .count()
+                    };
+                    if failed > 0 {
+                        egui::TopBottomPanel::bottom("left_panel_footer").show_inside(ui, |ui| {
+                            if ui
+                                .link(format!("\u{26a0} {failed} files failed to load"))
+                                .clicked()
+                            {
+                                self.errors_dialog = true;
+                            }
+                        });
+                    }
+                }
+
+                // Records which pass the type filter and search, in the order they're listed
+                let visible: Vec<usize> = match &self.metadata {
+                    Some(metadata) => {
+                        self.display_order
+                            .iter()
+                            .copied()
+                            .filter(|&index| {
+                                matches_type_filter(&self.type_filter, &metadata[index])
+                                    && self.search_results.as_ref().map_or(true, |results| {
+                                        results[index] != SearchResult::NoMatch
+                                    })
+                            })
+                            .collect()
+                    }
+                    None => Vec::new(),
+                };
+
+                // Move through the visible records with the arrow keys, unless a text box has focus
+                let mut keyed = None;
+                if !visible.is_empty() && !ui.ctx().wants_keyboard_input() {
+                    let position = visible.iter().position(|&index| index == self.selected);
+                    let last = visible.len() - 1;
+                    keyed = ui
+                        .input_mut(|i| {
+                            if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown) {
+                                Some(position.map_or(0, |p| (p + 1).min(last)))
+                            } else if i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp) {
+                                Some(position.map_or(0, |p| p.saturating_sub(1)))
+                            } else if i.consume_key(egui::Modifiers::NONE, egui::Key::Home) {
+                                Some(0)
+                            } else if i.consume_key(egui::Modifiers::NONE, egui::Key::End) {
+                                Some(last)
+                            } else {
+                                None
+                            }
+                        })
+                        .map(|p| visible[p])
+                        .filter(|&index| index != self.selected);
+                    if keyed.is_some() {
+                        clicked = keyed;
+                    }
+                }
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        let selected = self.selected;
+
+                        if let Some(metadata) = &self.metadata {
+                            for &index in &visible {
+                                let record = &metadata[index];
+                                let name = record.path.file_name().unwrap().to_string_lossy();
+                                // Mark files which failed to load, once they've been tried
+                                let error = record.get().and_then(|data| data.error.as_ref());
+                                let text = match error {
+                                    Some(_) => egui::RichText::new(format!("\u{26a0} {name}"))
+                                        .color(ui.visuals().error_fg_color),
+                                    None => egui::RichText::new(&*name),
+                                };
+                                let mut response = ui
+                                    .horizontal(|ui| {
+                                        let response = ui.add(egui::widgets::SelectableLabel::new(
+                                            index == selected
+                                                || Some(index) == self.compare_selected,
+                                            text,
+                                        ));
+                                        if let Some(modified) = record.modified {
+                                            ui.weak(format_age(modified));
                                         }
+                                        response
                                     })
-                                    .collect(),
+                                    .inner;
+                                if let Some(error) = error {
+                                    response = response.on_hover_text(error);
+                                }
+                                response.context_menu(|ui| {
+                                    if ui.button("Copy file name").clicked() {
+                                        ui.ctx().copy_text(name.to_string());
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("Copy full path").clicked() {
+                                        ui.ctx().copy_text(record.path.display().to_string());
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("Open containing folder").clicked() {
+                                        // Models inside an archive open the archive's folder
+                                        let folder =
+                                            record.path.ancestors().skip(1).find(|p| p.is_dir());
+                                        if let Some(Err(e)) =
+                                            folder.map(|folder| open_in_editor(folder, ""))
+                                        {
+                                            notice = Some(format!("Couldn't open the folder: {e}"));
+                                        }
+                                        ui.close_menu();
+                                    }
+                                });
+                                if keyed == Some(index) {
+                                    response.scroll_to_me(None);
+                                }
+                                if response.clicked() {
+                                    // Ctrl-click pins a second model for comparison
+                                    if ui.input(|i| i.modifiers.command) {
+                                        pinned = Some(index);
+                                    } else {
+                                        clicked = Some(index);
+                                    }
+                                }
+                            }
+                        }
+                    });
+            });
+        }
+
+        // Show a summary of all files in the store that couldn't be loaded
+        if self.errors_dialog {
+            if let Some(metadata) = &self.metadata {
+                let (loaded, total) = self.load_job.as_ref().map_or((0, 0), |job| job.progress());
+                ctx.show_viewport_immediate(
+                    egui::ViewportId::from_hash_of("errors_window"),
+                    egui::ViewportBuilder::default()
+                        .with_title("Load errors")
+                        .with_inner_size([600.0, 300.0]),
+                    |ctx, _class| {
+                        if ctx.input(|i| i.viewport().close_requested()) {
+                            self.errors_dialog = false;
+                        }
+                        egui::CentralPanel::default().show(ctx, |ui| {
+                            if loaded < total || total == 0 {
+                                ui.label("Waiting for the scan to finish");
+                                return;
+                            }
+                            egui::ScrollArea::vertical()
+                                .auto_shrink([false, false])
+                                .show(ui, |ui| {
+                                    egui::Grid::new("errors").num_columns(2).striped(true).show(
+                                        ui,
+                                        |ui| {
+                                            for (index, record) in metadata.iter().enumerate() {
+                                                if let Some(error) = record
+                                                    .get()
+                                                    .and_then(|data| data.error.as_ref())
+                                                {
+                                                    if ui
+                                                        .link(
+                                                            record
+                                                                .path
+                                                                .file_name()
+                                                                .unwrap()
+                                                                .to_string_lossy(),
+                                                        )
+                                                        .clicked()
+                                                    {
+                                                        clicked = Some(index);
+                                                    }
+                                                    ui.add(egui::Label::new(error).wrap());
+                                                    ui.end_row();
+                                                }
+                                            }
+                                        },
+                                    )
+                                })
+                        });
+                    },
+                );
+            } else {
+                self.errors_dialog = false;
+            }
+        }
+
+        if resort {
+            self.update_display_order();
+        }
+        if let Some(index) = clicked {
+            self.select(index);
+        }
+        if notice.is_some() {
+            self.notice = notice;
+        }
+        if let Some(index) = pinned {
+            self.compare_selected = Some(index);
+            self.prioritize(index);
+        }
+
+        // Get a reference to the selected entry, if it exists. The metadata is guaranteed to be
+        // defined once loaded, even if the file couldn't be parsed
+        let selected = self.metadata.as_ref().and_then(|m| m.get(self.selected));
+        let selected_data = selected.and_then(|record| record.get());
+        let compared = self
+            .compare_selected
+            .filter(|&index| index != self.selected)
+            .and_then(|index| self.metadata.as_ref().and_then(|m| m.get(index)));
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("LoRA Metadata Viewer");
+
+            if let Some(notice) = &self.notice {
+                let mut dismissed = false;
+                ui.horizontal(|ui| {
+                    ui.colored_label(Color32::YELLOW, notice);
+                    dismissed = ui.small_button("\u{2716}").clicked();
+                });
+                if dismissed {
+                    self.notice = None;
+                }
+            }
+
+            if let (Some(record), Some(other)) = (selected, compared) {
+                match (selected_data, other.get()) {
+                    (Some(data), Some(other_data)) => {
+                        if comparison_ui(ui, (record, data), (other, other_data)) {
+                            self.compare_selected = None;
+                        }
+                    }
+                    _ => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("Loading metadata\u{2026}");
+                        });
+                    }
+                }
+                return;
+            }
+
+            if let Some(title) = selected_data.and_then(|metadata| metadata.title.as_ref()) {
+                ui.label(egui::RichText::new(title).size(18.0).strong());
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Model name: ");
+                if let Some(record) = selected {
+                    ui.label(record.path.file_stem().unwrap().to_string_lossy());
+                    if ui.button("Full metadata").clicked() {
+                        self.metadata_dialog = true;
+                    }
+                    if ui.button("Tensors").clicked() {
+                        self.tensors_dialog = true;
+                        self.tensor_filter.clear();
+                    }
+                    if self.debug && ui.button("Tensor types").clicked() {
+                        self.tensor_types_dialog = true;
+                    }
+                    let tags = selected_data
+                        .map(|metadata| &metadata.tag_frequencies)
+                        .filter(|tags| !tags.is_empty());
+                    ui.add_enabled_ui(tags.is_some(), |ui| {
+                        ui.menu_button("Export tags", |ui| {
+                            ui.radio_value(
+                                &mut self.tag_export_format,
+                                TagExportFormat::WithFrequency,
+                                "Tag, frequency",
+                            );
+                            ui.radio_value(
+                                &mut self.tag_export_format,
+                                TagExportFormat::TagsOnly,
+                                "Tags only",
                             );
+                            if ui.button("Save\u{2026}").clicked() {
+                                let stem = record.path.file_stem().unwrap().to_string_lossy();
+                                let mut dialog = FileDialog::save_file(
+                                    record.path.parent().map(Path::to_path_buf),
+                                )
+                                .default_filename(format!("{stem}.tags.txt"));
+                                dialog.open();
+                                self.export_dialog =
+                                    Some((dialog, tags.cloned().unwrap_or_default()));
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                    if ui.button("Analysis").clicked() {
+                        self.analysis = Analysis::new(&record.path).ok();
+                    }
+                    // The tensors are loaded from the file itself, which rules out archives and GGUF
+                    let readable = record.size.is_some() && !is_gguf(&record.path);
+                    if ui
+                        .add_enabled(
+                            readable && self.pending_weight_stats.is_none(),
+                            egui::Button::new("Weight stats"),
+                        )
+                        .on_hover_text("Reads every tensor, which can be slow for large files")
+                        .on_disabled_hover_text("Only safetensors files outside archives")
+                        .clicked()
+                    {
+                        self.weight_stats = None;
+                        self.pending_weight_stats =
+                            Some(WeightStats::start(&record.path, ctx.clone()));
+                    }
+                    if self.pending_weight_stats.is_some() {
+                        ui.spinner();
+                    }
+                    let expected = selected_data.and_then(|metadata| metadata.stored_sha256());
+                    // Both hashes cover the tensor data, which is everything after the header
+                    let data_len = record
+                        .size
+                        .zip(selected_data)
+                        .map(|(size, metadata)| size.saturating_sub(metadata.header_size));
+                    if ui
+                        .add_enabled(
+                            expected.is_some() && data_len.is_some() && self.hash_check.is_none(),
+                            egui::Button::new("Verify hash"),
+                        )
+                        .on_hover_text("Check the tensor data against the hash in the metadata")
+                        .on_disabled_hover_text("The metadata doesn't include a hash")
+                        .clicked()
+                    {
+                        if let (Some(expected), Some(len)) = (expected, data_len) {
+                            let job = HashJob::start(&record.path, len, data_sha256, ctx.clone());
+                            self.hash_check = Some((expected, job));
+                        }
+                    }
+                    if ui
+                        .add_enabled(
+                            data_len.is_some() && self.fingerprint.is_none(),
+                            egui::Button::new("Fingerprint"),
+                        )
+                        .on_hover_text("Hash the tensors, ignoring the metadata")
+                        .clicked()
+                    {
+                        if let Some(len) = data_len {
+                            self.fingerprint = Some(HashJob::start(
+                                &record.path,
+                                len,
+                                content_fingerprint,
+                                ctx.clone(),
+                            ));
+                        }
+                    }
+                    if ui
+                        .button("Open in editor")
+                        .on_hover_text("Open the header JSON in an external editor")
+                        .clicked()
+                    {
+                        if let Err(e) = open_header_in_editor(
+                            &record.path,
+                            &self.editor,
+                            self.parse_options.header_limit,
+                        ) {
+                            self.notice = Some(format!("Couldn't open the header: {e}"));
+                        }
+                    }
+                }
+            });
+
+            // Only the selected model's preview is shown, so images are decoded as they're needed
+            if let Some(preview) = selected.and_then(|record| record.preview.as_ref()) {
+                ui.add(
+                    egui::Image::new(format!("file://{}", preview.display()))
+                        .max_height(256.0)
+                        .max_width(ui.available_width()),
+                );
+            }
+
+            // The background loader will get to the selected record eventually, so wait for it
+            // rather than showing empty metadata in the meantime
+            if selected.is_some() && selected_data.is_none() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Loading metadata\u{2026}");
+                });
+                return;
+            }
+
+            if let Some(error) = selected_data.and_then(|metadata| metadata.error.as_ref()) {
+                ui.colored_label(Color32::RED, format!("Failed to load: {error}"));
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Model type: ");
+                if let Some(metadata) = selected_data {
+                    for model in &metadata.model_types {
+                        ui.label(model.to_string());
+                    }
+                    if metadata.rank_stabilized() {
+                        ui.label("(rank-stabilized)").on_hover_text(
+                            "Scaled by alpha / \u{221a}rank rather than alpha / rank",
+                        );
+                    }
+                    // Fall back to the declared architecture if the tensors weren't recognized
+                    if metadata.model_types.is_empty() {
+                        if let Some(architecture) = &metadata.model_spec.architecture {
+                            ui.label(architecture)
+                                .on_hover_text("From modelspec.architecture");
+                        }
+                    }
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Base checkpoint: ");
+                if let Some(metadata) = selected_data {
+                    // A guess from the tensors is marked so it isn't mistaken for the metadata
+                    match (&metadata.base_model, metadata.inferred_base()) {
+                        (Some(base), _) => ui.label(base),
+                        (None, Some(inferred)) => ui
+                            .label(format!("\u{2248} {inferred}"))
+                            .on_hover_text("Inferred from the tensors, not stated in the metadata"),
+                        (None, None) => ui.label("Unknown"),
+                    };
+                }
+            });
+
+            if let Some(spec) = selected_data.map(|metadata| &metadata.model_spec) {
+                let mut parts = Vec::new();
+                if let Some(resolution) = &spec.resolution {
+                    parts.push(format!("Resolution: {resolution}"));
+                }
+                if let Some(implementation) = &spec.implementation {
+                    parts.push(format!("Implementation: {implementation}"));
+                }
+                if !parts.is_empty() {
+                    ui.label(parts.join("  "));
+                }
+            }
+
+            if let Some(text) = selected_data.and_then(|metadata| metadata.activation_text.as_ref())
+            {
+                ui.horizontal(|ui| {
+                    ui.label("Activation text: ");
+                    ui.add(egui::Label::new(egui::RichText::new(text).strong()).selectable(true));
+                    if ui
+                        .small_button("\u{1f4cb}")
+                        .on_hover_text("Copy activation text")
+                        .clicked()
+                    {
+                        ui.ctx().copy_text(text.clone());
+                    }
+                });
+            }
+
+            if let Some(metadata) = selected_data {
+                let mut parts = Vec::new();
+                if let Some(rank) = rank_text(metadata) {
+                    parts.push(format!("Rank: {rank}"));
+                }
+                if let Some(alpha) = metadata.network_alpha {
+                    parts.push(format!("Alpha: {alpha}"));
+                }
+                if let Some(scale) = scale_text(metadata) {
+                    parts.push(format!("Effective scale: {scale}"));
+                }
+                if let Some(vectors) = metadata.embedding_vectors() {
+                    parts.push(format!("Vectors: {vectors}"));
+                }
+                if !parts.is_empty() {
+                    ui.label(parts.join("  "));
+                }
+            }
+
+            if let Some(metadata) = selected_data.filter(|metadata| metadata.error.is_none()) {
+                let params = match metadata.parameter_count() {
+                    0 => "\u{2014}".to_string(),
+                    count => format!("{} params", format_count(count)),
+                };
+                let mut summary = format!(
+                    "Parameters: {params}  Size: {}",
+                    format_size(metadata.file_size)
+                );
+                if let Some(precision) = metadata.precision() {
+                    summary.push_str(&format!("  Precision: {precision}"));
+                }
+                ui.label(summary);
+                if metadata.header_size > 0 {
+                    ui.label(format!(
+                        "Header: {}, Data: {}",
+                        format_size(metadata.header_size),
+                        format_size(metadata.file_size.saturating_sub(metadata.header_size))
+                    ))
+                    .on_hover_text("The header holds the metadata and the list of tensors");
+                }
+                ui.label(structure_text(metadata));
+            }
+
+            if let Some(metadata) = selected_data.filter(|metadata| metadata.is_unrecognized()) {
+                ui.colored_label(Color32::YELLOW, "\u{26a0} Unrecognized model structure");
+                if metadata.tensors.is_empty() {
+                    ui.label("The file contains no tensors.");
+                } else {
+                    if let Some((prefix, count)) = metadata.common_tensor_prefix() {
+                        ui.label(format!(
+                            "No tensor names matched a known format. The most common prefix is \
+                             {prefix} ({count} of {} tensors).",
+                            metadata.tensors.len()
+                        ));
+                    }
+                    ui.label("Sample tensor names:");
+                    for (name, _, _) in metadata.tensors.iter().take(5) {
+                        ui.monospace(name);
+                    }
+                }
+            }
+
+            if let Some(metadata) = selected_data {
+                let warnings = metadata.warnings();
+                if !warnings.is_empty() {
+                    egui::CollapsingHeader::new(
+                        egui::RichText::new(format!("\u{26a0} Warnings ({})", warnings.len()))
+                            .color(Color32::YELLOW),
+                    )
+                    .id_source("warnings")
+                    .show(ui, |ui| {
+                        for warning in warnings {
+                            ui.add(egui::Label::new(warning).wrap());
                         }
+                    });
+                }
+            }
+
+            let lora = selected_data.filter(|metadata| {
+                metadata
+                    .model_types
+                    .iter()
+                    .any(|model_type| matches!(model_type, ModelType::Lora(_)))
+            });
+            if let Some(metadata) = lora {
+                ui.horizontal(|ui| {
+                    ui.label("Text encoder: ");
+                    if metadata.trains_text_encoder() {
+                        ui.colored_label(Color32::LIGHT_GREEN, "trained");
+                    } else {
+                        ui.colored_label(Color32::GRAY, "frozen")
+                            .on_hover_text("The file has no text encoder weights");
                     }
+                });
+                if metadata.has_unet() {
+                    ui.horizontal(|ui| {
+                        ui.label("LoCon: ");
+                        if metadata.has_conv_layers() {
+                            let details: Vec<_> = [
+                                metadata.conv_dim().map(|dim| format!("conv dim {dim}")),
+                                metadata.conv_alpha().map(|alpha| format!("alpha {alpha}")),
+                            ]
+                            .into_iter()
+                            .flatten()
+                            .collect();
+                            if details.is_empty() {
+                                ui.label("yes");
+                            } else {
+                                ui.label(format!("yes ({})", details.join(", ")));
+                            }
+                        } else {
+                            ui.colored_label(Color32::GRAY, "no").on_hover_text(
+                                "Only attention layers are trained, not convolutions",
+                            );
+                        }
+                    });
+                }
+            }
 
-                    egui::ScrollArea::vertical()
-                        .auto_shrink([false, false])
-                        .show(ui, |ui| {
-                            let selected = self.selected;
-
-                            if let Some(metadata) = &self.metadata {
-                                for (index, (path, _)) in metadata.iter().enumerate() {
-                                    if self.search_results.is_none()
-                                        || self.search_results.as_ref().unwrap()[index]
-                                            != SearchResult::NoMatch
-                                    {
-                                        if ui
-                                            .add(egui::widgets::SelectableLabel::new(
-                                                index == selected,
-                                                path.file_name().unwrap().to_string_lossy(),
-                                            ))
-                                            .clicked()
-                                        {
-                                            self.selected = index;
-                                            self.metadata_dialog = false;
-                                            self.tensors_dialog = false;
-                                        };
-                                    }
+            if let Some(ratio) = selected_data.and_then(|metadata| metadata.text_encoder_ratio()) {
+                ui.horizontal(|ui| {
+                    ui.label("TE/UNet parameter ratio: ");
+                    ui.label(format!("{ratio:.3}"));
+                });
+            }
+
+            if let Some(metadata) = selected_data {
+                let training = metadata.training.entries();
+                if !training.is_empty() {
+                    egui::CollapsingHeader::new("Training details").show(ui, |ui| {
+                        egui::Grid::new("training")
+                            .num_columns(2)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (label, value) in training {
+                                    ui.label(label);
+                                    ui.label(value);
+                                    ui.end_row();
                                 }
+                            });
+                    });
+                }
+            }
+
+            if let Some(metadata) = selected_data.filter(|m| !m.network_args.is_empty()) {
+                egui::CollapsingHeader::new("Network args").show(ui, |ui| {
+                    egui::Grid::new("network_args")
+                        .num_columns(2)
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (key, value) in &metadata.network_args {
+                                ui.label(key);
+                                ui.label(value);
+                                ui.end_row();
                             }
                         });
                 });
             }
-        }
-
-        // Get a reference to the selected entry, if it exists. The metadata is guaranteed to be
-        // defined, even if the file couldn't be loaded
-        let selected = self.metadata.as_ref().and_then(|m| m.get(self.selected));
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("LoRA Metadata Viewer");
+            if let Some(metadata) = selected_data.filter(|m| !m.training.buckets.is_empty()) {
+                let buckets = &metadata.training.buckets;
+                let total: u64 = buckets.iter().map(|bucket| bucket.images).sum();
+                let most = buckets
+                    .iter()
+                    .map(|bucket| bucket.images)
+                    .max()
+                    .unwrap_or(0);
+                egui::CollapsingHeader::new("Resolution buckets").show(ui, |ui| {
+                    egui::Grid::new("buckets").num_columns(2).show(ui, |ui| {
+                        for bucket in buckets {
+                            ui.label(format!("{}\u{d7}{}", bucket.width, bucket.height));
+                            let share = bucket.images as f64 / total.max(1) as f64;
+                            frequency_bar(
+                                ui,
+                                bucket.images as f32 / most.max(1) as f32,
+                                format!("{} ({:.1}%)", bucket.images, share * 100.0),
+                            );
+                            ui.end_row();
+                        }
+                    });
+                });
+            }
 
-            ui.horizontal(|ui| {
-                ui.label("Model name: ");
-                if let Some(metadata) = selected {
-                    ui.label(metadata.0.file_stem().unwrap().to_string_lossy());
-                    if ui.button("Full metadata").clicked() {
-                        self.metadata_dialog = true;
-                    }
-                    if ui.button("Tensors").clicked() {
-                        self.tensors_dialog = true;
-                    }
-                    if ui.button("Analysis").clicked() {
-                        if let Some(metadata) = selected {
-                            self.analysis = Analysis::new(&metadata.0).ok();
+            if let Some((expected, check)) = &mut self.hash_check {
+                check.poll();
+                ui.horizontal(|ui| {
+                    ui.label("Hash: ");
+                    match &check.outcome {
+                        None => check.progress_bar(ui),
+                        Some(Ok(hash)) if hash == expected => {
+                            ui.colored_label(Color32::GREEN, "\u{2714} Matches the metadata")
+                                .on_hover_text(hash);
+                        }
+                        Some(Ok(hash)) => {
+                            ui.colored_label(Color32::RED, "\u{2718} Doesn't match the metadata")
+                                .on_hover_text(format!("Expected {expected}\nCalculated {hash}"));
+                        }
+                        Some(Err(e)) => {
+                            ui.colored_label(Color32::RED, format!("Couldn't hash the file: {e}"));
                         }
                     }
-                }
-            });
+                });
+            }
 
-            ui.horizontal(|ui| {
-                ui.label("Model type: ");
-                if let Some(metadata) = selected {
-                    for model in &metadata.1.model_types {
-                        ui.label(model.to_string());
+            if let Some(fingerprint) = &mut self.fingerprint {
+                fingerprint.poll();
+                ui.horizontal(|ui| {
+                    ui.label("Fingerprint: ");
+                    match &fingerprint.outcome {
+                        None => fingerprint.progress_bar(ui),
+                        Some(Ok(hash)) => {
+                            ui.add(
+                                egui::Label::new(egui::RichText::new(hash).monospace())
+                                    .selectable(true),
+                            );
+                        }
+                        Some(Err(e)) => {
+                            ui.colored_label(Color32::RED, e);
+                        }
                     }
-                }
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("Base checkpoint: ");
-                if let Some((_, metadata)) = selected {
-                    ui.label(
-                        metadata
-                            .base_model
-                            .as_ref()
-                            .unwrap_or(&"Unknown".to_string()),
-                    );
-                }
-            });
+                });
+            }
 
             ui.separator();
 
-            if let Some((_, metadata)) = selected {
+            if let Some(metadata) = selected_data {
+                // Show either the merged tags or those of a single dataset directory
+                let dir = self.tag_dir.as_ref().and_then(|name| {
+                    metadata
+                        .dir_tag_frequencies
+                        .iter()
+                        .find(|(dir, _)| dir == name)
+                });
+                let (tags, images) = match dir {
+                    Some((name, tags)) => {
+                        let images = metadata
+                            .training
+                            .dataset_dirs
+                            .iter()
+                            .find(|dir| &dir.name == name)
+                            .and_then(|dir| dir.images)
+                            .filter(|&images| images > 0);
+                        (tags, images)
+                    }
+                    None => (&metadata.tag_frequencies, metadata.training.image_count()),
+                };
+                if !metadata.tag_frequencies.is_empty() {
+                    let total: f64 = tags.iter().map(|(_, freq)| freq).sum();
+                    ui.horizontal(|ui| {
+                        let mut summary =
+                            format!("Tags: {} unique, {total} total occurrences", tags.len());
+                        if let Some(images) = images {
+                            summary.push_str(&format!(" across {images} images"));
+                        }
+                        ui.label(summary);
+                        if ui
+                            .button("Copy tags")
+                            .on_hover_text("Copy the most frequent tags as a prompt")
+                            .clicked()
+                        {
+                            let prompt: Vec<_> = tags
+                                .iter()
+                                .take(self.copy_tag_count.0)
+                                .map(|(tag, _)| tag.as_str())
+                                .collect();
+                            ui.ctx().copy_text(prompt.join(", "));
+                        }
+                        ui.add(
+                            egui::Slider::new(&mut self.copy_tag_count.0, 1..=200).text("top tags"),
+                        );
+                        ui.checkbox(&mut self.tag_chart, "Chart");
+                        if metadata.dir_tag_frequencies.len() > 1 {
+                            egui::ComboBox::from_id_source("tag_dir")
+                                .selected_text(self.tag_dir.as_deref().unwrap_or("All"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.tag_dir, None, "All");
+                                    for (name, _) in &metadata.dir_tag_frequencies {
+                                        ui.selectable_value(
+                                            &mut self.tag_dir,
+                                            Some(name.clone()),
+                                            name,
+                                        );
+                                    }
+                                });
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let most = tags.first().map_or(1.0, |(_, freq)| *freq).max(1.0);
+                        ui.add(
+                            egui::Slider::new(&mut self.min_tag_frequency.0, 1.0..=most)
+                                .logarithmic(true)
+                                .clamp_to_range(false)
+                                .text("minimum frequency"),
+                        );
+                        let shown =
+                            tags.partition_point(|(_, freq)| *freq >= self.min_tag_frequency.0);
+                        if shown < tags.len() {
+                            ui.label(format!("(showing {shown} of {} tags)", tags.len()));
+                        }
+                    });
+                }
+                // The tags are sorted by descending frequency, so stop at the first one below
+                // the threshold
+                let min_frequency = self.min_tag_frequency.0;
+                let visible = tags
+                    .iter()
+                    .take_while(move |(_, freq)| *freq >= min_frequency);
                 egui::ScrollArea::vertical()
                     .auto_shrink([false, false])
                     .show(ui, |ui| {
+                        if self.tag_chart {
+                            let most = tags.iter().map(|(_, freq)| *freq).fold(0.0, f64::max);
+                            egui::Grid::new("tag_chart").num_columns(2).show(ui, |ui| {
+                                for (tag, freq) in visible.take(TAG_CHART_SIZE) {
+                                    ui.label(tag);
+                                    let fraction = if most > 0.0 { freq / most } else { 0.0 };
+                                    let bar = frequency_bar(ui, fraction as f32, freq)
+                                        .interact(egui::Sense::click())
+                                        .on_hover_text("Click to copy the tag");
+                                    if bar.clicked() {
+                                        ui.ctx().copy_text(tag.clone());
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                            return;
+                        }
+                        // The tags are already in the default order of descending frequency
+                        let mut sorted: Vec<_> = visible.collect();
+                        if self.tag_sort != TagColumn::Frequency || !self.tag_descending.0 {
+                            sorted.sort_by(|a, b| {
+                                let ordering = self.tag_sort.compare(a, b);
+                                if self.tag_descending.0 {
+                                    ordering.reverse()
+                                } else {
+                                    ordering
+                                }
+                            });
+                        }
+                        // Without an image count the raw frequencies are all we can show
                         egui::Grid::new("tags")
                             .num_columns(3)
                             .striped(true)
                             .show(ui, |ui| {
-                                for (tag, freq) in &metadata.tag_frequencies {
+                                for column in TagColumn::ALL {
+                                    let label = if column != self.tag_sort {
+                                        column.label().to_string()
+                                    } else if self.tag_descending.0 {
+                                        format!("{} \u{2b07}", column.label())
+                                    } else {
+                                        format!("{} \u{2b06}", column.label())
+                                    };
+                                    if ui.button(label).clicked() {
+                                        if column == self.tag_sort {
+                                            self.tag_descending.0 = !self.tag_descending.0;
+                                        } else {
+                                            self.tag_sort = column;
+                                            self.tag_descending.0 = column == TagColumn::Frequency;
+                                        }
+                                    }
+                                }
+                                ui.end_row();
+                                for (tag, freq) in sorted {
                                     ui.label(tag);
-                                    ui.label(format!("{freq}"));
+                                    match images {
+                                        Some(images) => ui.label(format!(
+                                            "{freq}  ({:.0}%)",
+                                            freq / images as f64 * 100.0
+                                        )),
+                                        None => ui.label(format!("{freq}")),
+                                    };
                                     ui.allocate_space(egui::vec2(ui.available_width(), 0.0));
                                     ui.end_row();
                                 }
@@ -426,7 +3519,57 @@ impl eframe::App for App {
             }
         });
 
-        if let Some((_, metadata)) = selected {
+        // Write the CSV report once a destination has been chosen
+        if let Some((dialog, records)) = &mut self.report_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(path) = dialog.path() {
+                    let (send, written) = channel();
+                    let (path, records, ctx) = (path.to_path_buf(), records.clone(), ctx.clone());
+                    let fingerprints = self.report_fingerprints;
+                    thread::spawn(move || {
+                        send.send(
+                            std::fs::File::create(path)
+                                .and_then(|file| write_report(&records, fingerprints, file)),
+                        )
+                        .ok();
+                        ctx.request_repaint();
+                    });
+                    self.pending_report = Some(written);
+                }
+            }
+            if !dialog.visible() {
+                self.report_dialog = None;
+            }
+        }
+        if let Some(written) = &self.pending_report {
+            match written.try_recv() {
+                Ok(result) => {
+                    self.pending_report = None;
+                    if let Err(e) = result {
+                        self.notice = Some(format!("Couldn't write the report: {e}"));
+                    }
+                }
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => self.pending_report = None,
+            }
+        }
+
+        // Write the exported tags once a destination has been chosen
+        if let Some((dialog, tags)) = &mut self.export_dialog {
+            if dialog.show(ctx).selected() {
+                if let Some(path) = dialog.path() {
+                    if let Err(e) = std::fs::write(path, format_tags(tags, self.tag_export_format))
+                    {
+                        self.notice = Some(format!("Couldn't export tags: {e}"));
+                    }
+                }
+            }
+            if !dialog.visible() {
+                self.export_dialog = None;
+            }
+        }
+
+        if let Some(metadata) = selected_data {
             if self.metadata_dialog {
                 ctx.show_viewport_immediate(
                     egui::ViewportId::from_hash_of("metadata_window"),
@@ -437,19 +3580,77 @@ impl eframe::App for App {
                         if ctx.input(|i| i.viewport().close_requested()) {
                             self.metadata_dialog = false;
                         }
-                        let mut metadata: Vec<_> = metadata.raw_metadata.iter().collect();
+                        let raw_metadata = &metadata.raw_metadata;
+                        let mut metadata: Vec<_> = raw_metadata.iter().collect();
                         metadata.sort();
                         egui::CentralPanel::default().show(ctx, |ui| {
+                            ui.horizontal(|ui| {
+                                if ui.button("Copy as JSON").clicked() {
+                                    ui.ctx().copy_text(metadata_json(
+                                        raw_metadata,
+                                        self.nest_metadata_json.0,
+                                    ));
+                                }
+                                ui.checkbox(&mut self.nest_metadata_json.0, "Expand nested JSON")
+                                    .on_hover_text(
+                                        "Embed values which are JSON themselves rather than \
+                                         copying them as strings",
+                                    );
+                            });
                             egui::ScrollArea::vertical()
                                 .auto_shrink([false, false])
                                 .show(ui, |ui| {
                                     egui::Grid::new("metadata")
-                                        .num_columns(2)
+                                        .num_columns(3)
                                         .striped(true)
                                         .show(ui, |ui| {
                                             for (tag, value) in metadata {
                                                 ui.label(tag);
-                                                ui.add(egui::Label::new(value).wrap());
+                                                if ui
+                                                    .small_button("\u{1f4cb}")
+                                                    .on_hover_text("Copy value")
+                                                    .clicked()
+                                                {
+                                                    ui.ctx().copy_text(value.clone());
+                                                }
+                                                // JSON is only parsed once its cell is expanded,
+                                                // since some values are very large, and the result
+                                                // is kept until the selection changes
+                                                let trimmed = value.trim_start();
+                                                if trimmed.starts_with('{')
+                                                    || trimmed.starts_with('[')
+                                                {
+                                                    let mut preview: String =
+                                                        value.chars().take(60).collect();
+                                                    if preview.len() < value.len() {
+                                                        preview.push('\u{2026}');
+                                                    }
+                                                    egui::CollapsingHeader::new(preview)
+                                                        .id_source(("metadata_value", tag))
+                                                        .show(ui, |ui| {
+                                                            let pretty = self
+                                                                .pretty_metadata
+                                                                .entry(tag.clone())
+                                                                .or_insert_with(|| {
+                                                                    pretty_json(value)
+                                                                });
+                                                            match pretty {
+                                                                Some(pretty) => {
+                                                                    ui.add(egui::Label::new(
+                                                                        egui::RichText::new(
+                                                                            pretty.as_str(),
+                                                                        )
+                                                                        .monospace(),
+                                                                    ))
+                                                                }
+                                                                None => ui.add(
+                                                                    egui::Label::new(value).wrap(),
+                                                                ),
+                                                            }
+                                                        });
+                                                } else {
+                                                    ui.add(egui::Label::new(value).wrap());
+                                                }
                                                 ui.end_row();
                                             }
                                         })
@@ -468,34 +3669,127 @@ impl eframe::App for App {
                         if ctx.input(|i| i.viewport().close_requested()) {
                             self.tensors_dialog = false;
                         }
+                        egui::TopBottomPanel::bottom("tensor_total").show(ctx, |ui| {
+                            ui.label(format!(
+                                "Total: {} tensors, {} parameters",
+                                metadata.tensors.len(),
+                                format_count(metadata.parameter_count())
+                            ));
+                        });
                         egui::CentralPanel::default().show(ctx, |ui| {
+                            ui.add(
+                                TextEdit::singleline(&mut self.tensor_filter)
+                                    .hint_text("Filter tensors")
+                                    .desired_width(f32::INFINITY),
+                            );
+                            let filter = self.tensor_filter.to_lowercase();
+                            if self
+                                .tensor_rows
+                                .as_ref()
+                                .is_some_and(|rows| rows.filter != filter)
+                            {
+                                self.tensor_rows = None;
+                            }
+                            let rows = self
+                                .tensor_rows
+                                .get_or_insert_with(|| TensorRows::new(metadata, filter));
+                            let filtered = !rows.filter.is_empty();
                             egui::ScrollArea::vertical()
                                 .auto_shrink([false, false])
                                 .show(ui, |ui| {
-                                    egui::Grid::new("tensors")
-                                        .num_columns(3)
+                                    for (block, tensors) in &rows.blocks {
+                                        egui::CollapsingHeader::new(format!(
+                                            "{block} ({})",
+                                            tensors.len()
+                                        ))
+                                        .id_source(block)
+                                        .default_open(false)
+                                        .open(filtered.then_some(true))
+                                        .show(ui, |ui| {
+                                            let right =
+                                                egui::Layout::right_to_left(egui::Align::Center);
+                                            egui::Grid::new(("tensors", block))
+                                                .num_columns(5)
+                                                .striped(true)
+                                                .show(ui, |ui| {
+                                                    for [name, shape, dtype, count] in tensors {
+                                                        ui.label(name);
+                                                        ui.with_layout(right, |ui| ui.label(shape));
+                                                        ui.label(dtype);
+                                                        ui.with_layout(right, |ui| ui.label(count));
+                                                        ui.allocate_space(egui::vec2(
+                                                            ui.available_width(),
+                                                            0.0,
+                                                        ));
+                                                        ui.end_row();
+                                                    }
+                                                });
+                                        });
+                                    }
+                                })
+                        })
+                    },
+                );
+            }
+            if self.tensor_types_dialog {
+                ctx.show_viewport_immediate(
+                    egui::ViewportId::from_hash_of("tensor_types_window"),
+                    egui::ViewportBuilder::default()
+                        .with_title("Tensor types")
+                        .with_inner_size([700.0, 400.0]),
+                    |ctx, _class| {
+                        if ctx.input(|i| i.viewport().close_requested()) {
+                            self.tensor_types_dialog = false;
+                        }
+                        let types = self.tensor_types.get_or_insert_with(|| {
+                            metadata
+                                .tensor_types()
+                                .into_iter()
+                                .map(|(name, model_type)| (name.to_string(), model_type))
+                                .collect()
+                        });
+                        egui::TopBottomPanel::bottom("tensor_types_total").show(ctx, |ui| {
+                            let recognized = types.iter().filter(|(_, t)| t.is_some()).count();
+                            ui.label(format!(
+                                "{recognized} of {} tensors recognized, giving: {}",
+                                types.len(),
+                                model_types_text(metadata)
+                            ));
+                        });
+                        egui::CentralPanel::default().show(ctx, |ui| {
+                            egui::ScrollArea::both()
+                                .auto_shrink([false, false])
+                                .show(ui, |ui| {
+                                    egui::Grid::new("tensor_types")
+                                        .num_columns(2)
                                         .striped(true)
                                         .show(ui, |ui| {
-                                            for (name, shape) in &metadata.tensors {
-                                                ui.label(name);
-                                                let shape: Vec<_> =
-                                                    shape.iter().map(|v| format!("{v}")).collect();
-                                                ui.label(shape.join(", "));
-                                                ui.allocate_space(egui::vec2(
-                                                    ui.available_width(),
-                                                    0.0,
-                                                ));
+                                            for (name, model_type) in types.iter() {
+                                                ui.monospace(name);
+                                                match model_type {
+                                                    Some(model_type) => {
+                                                        ui.label(model_type.to_string())
+                                                    }
+                                                    None => ui.colored_label(Color32::GRAY, "None"),
+                                                };
                                                 ui.end_row();
                                             }
-                                        })
-                                })
-                        })
+                                        });
+                                });
+                        });
                     },
                 );
             }
-        } else {
+        } else if selected.is_none() {
             self.metadata_dialog = false;
             self.tensors_dialog = false;
+            self.tensor_types_dialog = false;
+        }
+        if !self.tensors_dialog {
+            self.tensor_rows = None;
+        }
+        if !self.tensor_types_dialog {
+            self.tensor_types = None;
         }
 
         if self.analysis.is_some() {
@@ -536,5 +3830,452 @@ impl eframe::App for App {
                 },
             );
         }
+
+        if let Some(result) = &self.pending_weight_stats {
+            match result.try_recv() {
+                Ok(result) => {
+                    self.pending_weight_stats = None;
+                    let name = self
+                        .metadata
+                        .as_ref()
+                        .and_then(|metadata| metadata.get(self.selected))
+                        .and_then(|record| record.path.file_name())
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    match result {
+                        Ok(stats) => self.weight_stats = Some((name, stats)),
+                        Err(e) => {
+                            self.notice = Some(format!("Couldn't calculate weight stats: {e}"))
+                        }
+                    }
+                }
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => self.pending_weight_stats = None,
+            }
+        }
+        if let Some((name, stats)) = &self.weight_stats {
+            let mut close = false;
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("weight_stats_window"),
+                egui::ViewportBuilder::default()
+                    .with_title(format!("Weight statistics: {name}"))
+                    .with_inner_size([600.0, 300.0]),
+                |ctx, _class| {
+                    if ctx.input(|i| i.viewport().close_requested()) {
+                        close = true;
+                    } else {
+                        egui::CentralPanel::default().show(ctx, |ui| {
+                            let (min, max, mean) = stats.overall;
+                            ui.label(format!(
+                                "All tensors: min {min:.6}  max {max:.6}  mean {mean:.6}"
+                            ));
+                            ui.separator();
+                            egui::ScrollArea::vertical()
+                                .auto_shrink([false, false])
+                                .show(ui, |ui| {
+                                    egui::Grid::new("weight_stats")
+                                        .num_columns(4)
+                                        .striped(true)
+                                        .show(ui, |ui| {
+                                            ui.strong("Tensor");
+                                            ui.strong("Min |w|");
+                                            ui.strong("Max |w|");
+                                            ui.strong("Mean |w|");
+                                            ui.end_row();
+                                            for (name, min, max, mean) in &stats.tensors {
+                                                ui.label(name);
+                                                ui.label(format!("{min:.6}"));
+                                                ui.label(format!("{max:.6}"));
+                                                ui.label(format!("{mean:.6}"));
+                                                ui.end_row();
+                                            }
+                                        })
+                                })
+                        });
+                    }
+                },
+            );
+            if close {
+                self.weight_stats = None;
+            }
+        }
+
+        if self.collection_dialog {
+            if let Some(metadata) = &self.metadata {
+                let (loaded, total) = self.load_job.as_ref().map_or((0, 0), |job| job.progress());
+                ctx.show_viewport_immediate(
+                    egui::ViewportId::from_hash_of("collection_window"),
+                    egui::ViewportBuilder::default()
+                        .with_title("Collection")
+                        .with_inner_size([900.0, 400.0]),
+                    |ctx, _class| {
+                        if ctx.input(|i| i.viewport().close_requested()) {
+                            self.collection_dialog = false;
+                        }
+                        egui::CentralPanel::default().show(ctx, |ui| {
+                            if loaded < total || total == 0 {
+                                ui.label("Waiting for the scan to finish");
+                                return;
+                            }
+
+                            // Records left unloaded by a cancelled scan are skipped
+                            let mut order: Vec<_> = (0..metadata.len())
+                                .filter(|&index| metadata[index].get().is_some())
+                                .collect();
+                            order.sort_by(|&a, &b| {
+                                let ordering =
+                                    self.collection_sort.compare(&metadata[a], &metadata[b]);
+                                if self.collection_descending {
+                                    ordering.reverse()
+                                } else {
+                                    ordering
+                                }
+                            });
+
+                            TableBuilder::new(ui)
+                                .striped(true)
+                                .resizable(true)
+                                .column(Column::initial(200.0).clip(true))
+                                .columns(Column::auto(), CollectionColumn::ALL.len() - 1)
+                                .header(20.0, |mut header| {
+                                    for column in CollectionColumn::ALL {
+                                        header.col(|ui| {
+                                            let label = if column != self.collection_sort {
+                                                column.label().to_string()
+                                            } else if self.collection_descending {
+                                                format!("{} \u{2b07}", column.label())
+                                            } else {
+                                                format!("{} \u{2b06}", column.label())
+                                            };
+                                            if ui.button(label).clicked() {
+                                                if column == self.collection_sort {
+                                                    self.collection_descending =
+                                                        !self.collection_descending;
+                                                } else {
+                                                    self.collection_sort = column;
+                                                    self.collection_descending = false;
+                                                }
+                                            }
+                                        });
+                                    }
+                                })
+                                .body(|body| {
+                                    body.rows(18.0, order.len(), |mut row| {
+                                        let record = &metadata[order[row.index()]];
+                                        let data = &*record.data;
+                                        let optional = |value: Option<String>| {
+                                            value.unwrap_or_else(|| "\u{2014}".to_string())
+                                        };
+                                        row.col(|ui| {
+                                            ui.label(
+                                                record.path.file_name().unwrap().to_string_lossy(),
+                                            );
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(model_types_text(data));
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(optional(data.base_model.clone()));
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(optional(
+                                                data.network_dim.map(|d| d.to_string()),
+                                            ));
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(optional(
+                                                data.network_alpha.map(|a| a.to_string()),
+                                            ));
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(optional(
+                                                network_scale(data).map(|s| format!("{s:.3}")),
+                                            ));
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(data.tag_frequencies.len().to_string());
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(format_size(data.file_size));
+                                        });
+                                    });
+                                });
+                        });
+                    },
+                );
+            } else {
+                self.collection_dialog = false;
+            }
+        }
+
+        if self.tag_usage_dialog {
+            if let Some(metadata) = &self.metadata {
+                let (loaded, total) = self.load_job.as_ref().map_or((0, 0), |job| job.progress());
+                let done = !(loaded < total || total == 0);
+                if done && self.tag_usage.is_none() {
+                    // Already sorted by model count, which is what the sort is reset to
+                    let usage = tag_usage(metadata.iter().filter_map(|record| record.get()));
+                    self.tag_usage = Some(usage);
+                    self.tag_usage_sort = TagUsageColumn::default();
+                    self.tag_usage_descending.0 = true;
+                }
+                ctx.show_viewport_immediate(
+                    egui::ViewportId::from_hash_of("tag_usage_window"),
+                    egui::ViewportBuilder::default()
+                        .with_title("All tags")
+                        .with_inner_size([500.0, 500.0]),
+                    |ctx, _class| {
+                        if ctx.input(|i| i.viewport().close_requested()) {
+                            self.tag_usage_dialog = false;
+                        }
+                        egui::CentralPanel::default().show(ctx, |ui| {
+                            let Some(usage) = &mut self.tag_usage else {
+                                ui.label("Waiting for the scan to finish");
+                                return;
+                            };
+                            ui.add(
+                                TextEdit::singleline(&mut self.tag_usage_filter)
+                                    .hint_text("Filter tags")
+                                    .desired_width(f32::INFINITY),
+                            );
+                            let filter = self.tag_usage_filter.to_lowercase();
+                            let rows: Vec<_> = usage
+                                .iter()
+                                .filter(|usage| usage.tag.to_lowercase().contains(&filter))
+                                .collect();
+                            ui.label(format!("{} of {} tags", rows.len(), usage.len()));
+
+                            let mut sort = None;
+                            TableBuilder::new(ui)
+                                .striped(true)
+                                .resizable(true)
+                                .column(Column::remainder().clip(true))
+                                .columns(Column::auto(), 2)
+                                .header(20.0, |mut header| {
+                                    for column in TagUsageColumn::ALL {
+                                        header.col(|ui| {
+                                            let label = if column != self.tag_usage_sort {
+                                                column.label().to_string()
+                                            } else if self.tag_usage_descending.0 {
+                                                format!("{} \u{2b07}", column.label())
+                                            } else {
+                                                format!("{} \u{2b06}", column.label())
+                                            };
+                                            if ui.button(label).clicked() {
+                                                sort = Some(column);
+                                            }
+                                        });
+                                    }
+                                })
+                                .body(|body| {
+                                    body.rows(18.0, rows.len(), |mut row| {
+                                        let usage = rows[row.index()];
+                                        row.col(|ui| {
+                                            ui.label(&usage.tag);
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(usage.models.to_string());
+                                        });
+                                        row.col(|ui| {
+                                            ui.label(format!("{}", usage.images));
+                                        });
+                                    });
+                                });
+
+                            if let Some(column) = sort {
+                                if column == self.tag_usage_sort {
+                                    self.tag_usage_descending.0 = !self.tag_usage_descending.0;
+                                } else {
+                                    self.tag_usage_sort = column;
+                                    // Counts are wanted biggest first, but tags alphabetically
+                                    self.tag_usage_descending.0 = column != TagUsageColumn::Tag;
+                                }
+                                usage.sort_by(|a, b| {
+                                    let ordering = self.tag_usage_sort.compare(a, b);
+                                    if self.tag_usage_descending.0 {
+                                        ordering.reverse()
+                                    } else {
+                                        ordering
+                                    }
+                                });
+                            }
+                        });
+                    },
+                );
+            } else {
+                self.tag_usage_dialog = false;
+            }
+        }
+
+        if self.settings_dialog {
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("settings_window"),
+                egui::ViewportBuilder::default()
+                    .with_title("Settings")
+                    .with_inner_size([360.0, 320.0]),
+                |ctx, _class| {
+                    if ctx.input(|i| i.viewport().close_requested()) {
+                        self.settings_dialog = false;
+                    }
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Theme");
+                            ui.radio_value(&mut self.theme, Theme::System, "System");
+                            ui.radio_value(&mut self.theme, Theme::Dark, "Dark");
+                            ui.radio_value(&mut self.theme, Theme::Light, "Light");
+                        });
+                        ui.checkbox(&mut self.auto_select_match, "Select first search match");
+                        if ui
+                            .checkbox(
+                                &mut self.parse_options.fold_tag_case,
+                                "Merge tags differing only by case",
+                            )
+                            .changed()
+                        {
+                            self.rescan();
+                        }
+                        if ui
+                            .checkbox(&mut self.parse_options.normalize_tags, "Normalize tags")
+                            .on_hover_text(
+                                "Merge tags which only differ by case or by underscores and \
+                                 spaces, such as long_hair and Long Hair",
+                            )
+                            .changed()
+                        {
+                            self.rescan();
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Auto-refresh folder every");
+                            ui.add(
+                                egui::DragValue::new(&mut self.refresh_interval)
+                                    .range(0..=3600)
+                                    .suffix(" s"),
+                            );
+                        })
+                        .response
+                        .on_hover_text("Set to 0 to disable");
+                        ui.checkbox(&mut self.watch_directory.0, "Watch folder for changes")
+                            .on_hover_text("Turn off for network shares which don't support it");
+                        ui.checkbox(
+                            &mut self.report_fingerprints,
+                            "Include fingerprints in CSV reports",
+                        )
+                        .on_hover_text("Reads every model in full, which is slow for checkpoints");
+                        if ui
+                            .checkbox(&mut self.recursive_scan, "Include subfolders")
+                            .changed()
+                        {
+                            self.rescan();
+                        }
+                        if ui
+                            .checkbox(
+                                &mut self.background_loading.0,
+                                "Load models in the background",
+                            )
+                            .on_hover_text("Otherwise models are only loaded when selected")
+                            .changed()
+                        {
+                            // Switching it on carries on with the current store instead of reloading
+                            if self.background_loading.0 {
+                                self.load_all();
+                            } else {
+                                self.rescan();
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Header size limit");
+                            let mut limit = self.parse_options.header_limit / 1048576;
+                            let response = ui.add(
+                                egui::DragValue::new(&mut limit)
+                                    .range(1..=4096)
+                                    .suffix(" MB"),
+                            );
+                            self.parse_options.header_limit = limit * 1048576;
+                            // Wait for a drag to finish, rather than rescanning at every step
+                            if response.drag_stopped()
+                                || (response.changed() && !response.dragged())
+                            {
+                                self.rescan();
+                            }
+                        })
+                        .response
+                        .on_hover_text("Larger headers are rejected as corrupt");
+                        ui.horizontal(|ui| {
+                            ui.label("External editor");
+                            ui.add(
+                                TextEdit::singleline(&mut self.editor).hint_text("System default"),
+                            );
+                        });
+                    });
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeds_nested_metadata_json() {
+        let metadata = [
+            ("ss_network_dim".to_string(), "16".to_string()),
+            (
+                "ss_dataset_dirs".to_string(),
+                r#"{"cat": {"n_repeats": 1}}"#.to_string(),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let flat: serde_json::Value =
+            serde_json::from_str(&metadata_json(&metadata, false)).unwrap();
+        assert_eq!(flat["ss_network_dim"], "16");
+        assert_eq!(flat["ss_dataset_dirs"], r#"{"cat": {"n_repeats": 1}}"#);
+        let nested: serde_json::Value =
+            serde_json::from_str(&metadata_json(&metadata, true)).unwrap();
+        assert_eq!(nested["ss_network_dim"], "16");
+        assert_eq!(nested["ss_dataset_dirs"]["cat"]["n_repeats"], 1);
+    }
+
+    #[test]
+    fn splits_editor_commands() {
+        assert_eq!(command_words("code  --wait"), vec!["code", "--wait"]);
+        assert_eq!(
+            command_words(r#""C:\Program Files\Notepad++\notepad++.exe" -multiInst"#),
+            vec![r"C:\Program Files\Notepad++\notepad++.exe", "-multiInst"]
+        );
+        assert_eq!(command_words(r#"a"b c"d """#), vec!["ab cd", ""]);
+        assert!(command_words("  ").is_empty());
+    }
+
+    #[test]
+    fn pretty_prints_json_values() {
+        let pretty = pretty_json(r#"{"cat": {"n_repeats": 1}}"#).unwrap();
+        assert!(pretty.lines().count() > 1);
+        assert_eq!(pretty_json("16"), None);
+        assert_eq!(pretty_json("not json"), None);
+    }
+
+    #[test]
+    fn counts_tag_usage_across_models() {
+        let model = |tags: &[(&str, f64)]| LoraData {
+            tag_frequencies: tags.iter().map(|(tag, n)| (tag.to_string(), *n)).collect(),
+            ..Default::default()
+        };
+        let models = [
+            model(&[("cat", 10.0), ("dog", 2.0)]),
+            model(&[("cat", 3.0)]),
+            model(&[]),
+        ];
+        let usage = |tag: &str, models: usize, images: f64| TagUsage {
+            tag: tag.to_string(),
+            models,
+            images,
+        };
+        assert_eq!(
+            tag_usage(&models),
+            vec![usage("cat", 2, 13.0), usage("dog", 1, 2.0)]
+        );
     }
 }
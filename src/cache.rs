@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use lora_view::metadata::{LoraData, ParseOptions};
+use serde::{Deserialize, Serialize};
+
+/// Version of the cache format and of the parsing which produced it
+///
+/// Bump this whenever `LoraData` or the way models are classified changes, so entries parsed by an
+/// older build aren't served. The cache is also discarded when the app version changes.
+const CACHE_VERSION: u32 = 2;
+
+/// Contents of the cache file
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CacheFile {
+    version: u32,
+    app_version: String,
+    entries: HashMap<PathBuf, CacheEntry>,
+}
+
+/// Just the versions of a cache file, which are checked before the entries are parsed
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct CacheVersions {
+    version: u32,
+    app_version: String,
+}
+
+/// A parsed model in the cache, with what's needed to tell whether it's still valid
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    modified: SystemTime,
+    size: u64,
+    options: ParseOptions,
+    data: Arc<LoraData>,
+}
+
+/// Parsed metadata of previously seen models, saved between sessions
+///
+/// Entries are keyed by path and only used while the file's modification time and size match, so
+/// reading the header can be skipped for models which haven't changed since they were cached.
+/// Entries for files which no longer exist are dropped when the cache is saved, so it doesn't
+/// grow forever, but those for folders which weren't opened this session are kept.
+#[derive(Default)]
+pub struct MetadataCache {
+    entries: Mutex<HashMap<PathBuf, CacheEntry>>,
+    /// Set when entries have been added since the cache was loaded or last saved
+    dirty: AtomicBool,
+}
+
+impl MetadataCache {
+    /// Location of the cache file in the app's storage directory, if there is one
+    pub fn default_path() -> Option<PathBuf> {
+        eframe::storage_dir(crate::APP_NAME).map(|dir| dir.join("metadata_cache.json"))
+    }
+
+    /// Read the cache from `path`, starting afresh if it was written by a different version
+    pub fn load(path: &Path) -> Result<MetadataCache> {
+        let text = fs::read_to_string(path)?;
+        // Entries from another version may not even deserialize, so check before reading them
+        let versions: CacheVersions = serde_json::from_str(&text)?;
+        if versions.version != CACHE_VERSION || versions.app_version != env!("CARGO_PKG_VERSION") {
+            return Ok(MetadataCache::default());
+        }
+        let file: CacheFile = serde_json::from_str(&text)?;
+        Ok(MetadataCache {
+            entries: Mutex::new(file.entries),
+            dirty: AtomicBool::new(false),
+        })
+    }
+
+    /// Write the cache to `path`, if it has changed since it was last written
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let result = (|| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|path, _| path.exists());
+            let file = CacheFile {
+                version: CACHE_VERSION,
+                app_version: env!("CARGO_PKG_VERSION").to_string(),
+                entries: std::mem::take(&mut *entries),
+            };
+            let text = serde_json::to_string(&file);
+            *entries = file.entries;
+            fs::write(path, text?)?;
+            Ok(())
+        })();
+        if result.is_err() {
+            self.dirty.store(true, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Get the cached data for a file, if the file hasn't changed since it was cached
+    pub fn get(
+        &self,
+        path: &Path,
+        modified: SystemTime,
+        size: u64,
+        options: ParseOptions,
+    ) -> Option<Arc<LoraData>> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(path)?;
+        (entry.modified == modified && entry.size == size && entry.options == options)
+            .then(|| entry.data.clone())
+    }
+
+    /// Add or replace the cached data for a file
+    pub fn insert(
+        &self,
+        path: PathBuf,
+        modified: SystemTime,
+        size: u64,
+        options: ParseOptions,
+        data: Arc<LoraData>,
+    ) {
+        let entry = CacheEntry {
+            modified,
+            size,
+            options,
+            data,
+        };
+        self.entries.lock().unwrap().insert(path, entry);
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn invalidates_changed_files() {
+        let cache = MetadataCache::default();
+        let path = PathBuf::from("a.safetensors");
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let options = ParseOptions::default();
+        cache.insert(path.clone(), modified, 10, options, Arc::default());
+
+        assert!(cache.get(&path, modified, 10, options).is_some());
+        assert!(cache
+            .get(Path::new("b.safetensors"), modified, 10, options)
+            .is_none());
+        let later = modified + Duration::from_secs(1);
+        assert!(cache.get(&path, later, 10, options).is_none());
+        assert!(cache.get(&path, modified, 11, options).is_none());
+        let folded = ParseOptions {
+            fold_tag_case: true,
+            ..options
+        };
+        assert!(cache.get(&path, modified, 10, folded).is_none());
+    }
+
+    #[test]
+    fn saves_existing_files_and_checks_the_version() {
+        let dir = std::env::temp_dir().join("lora_view_saves_existing_files");
+        fs::create_dir_all(&dir).unwrap();
+        let (model, gone) = (dir.join("model.safetensors"), dir.join("gone.safetensors"));
+        fs::write(&model, b"").unwrap();
+        let cache_path = dir.join("cache.json");
+        let modified = SystemTime::UNIX_EPOCH;
+        let options = ParseOptions::default();
+
+        let cache = MetadataCache::default();
+        for path in [&model, &gone] {
+            cache.insert(path.clone(), modified, 0, options, Arc::default());
+        }
+        cache.save(&cache_path).unwrap();
+        let loaded = MetadataCache::load(&cache_path).unwrap();
+        assert!(loaded.get(&model, modified, 0, options).is_some());
+        assert!(loaded.get(&gone, modified, 0, options).is_none());
+
+        // A cache from another version is discarded rather than read
+        let text = fs::read_to_string(&cache_path).unwrap();
+        let mut file: serde_json::Value = serde_json::from_str(&text).unwrap();
+        file["version"] = serde_json::json!(CACHE_VERSION - 1);
+        fs::write(&cache_path, file.to_string()).unwrap();
+        let loaded = MetadataCache::load(&cache_path).unwrap();
+        assert!(loaded.get(&model, modified, 0, options).is_none());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
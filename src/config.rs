@@ -0,0 +1,51 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use crate::app::{CollectionColumn, SidebarSort, Theme};
+
+/// Preferences read from the TOML config file
+///
+/// Every setting is optional. Settings present in the file override the values saved from the
+/// previous session when the app starts, but can still be changed while it's running. Unknown
+/// keys are an error, so a misspelt setting doesn't go unnoticed.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub auto_select_match: Option<bool>,
+    pub refresh_interval: Option<u32>,
+    pub watch_directory: Option<bool>,
+    pub fold_tag_case: Option<bool>,
+    pub normalize_tags: Option<bool>,
+    /// Header size limit in MB, as in the settings dialog
+    pub header_limit: Option<usize>,
+    pub hide_side_panel: Option<bool>,
+    pub sort: Option<CollectionColumn>,
+    pub sort_descending: Option<bool>,
+    pub list_sort: Option<SidebarSort>,
+    pub list_descending: Option<bool>,
+    pub editor: Option<String>,
+    pub theme: Option<Theme>,
+}
+
+impl Config {
+    /// Location of the config file in the platform config directory, if there is one
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("lora_view").join("config.toml"))
+    }
+
+    /// The header size limit in bytes, kept within the range the settings dialog allows
+    pub fn header_limit_bytes(&self) -> Option<usize> {
+        self.header_limit
+            .map(|limit| limit.clamp(1, 4096) * 1048576)
+    }
+
+    pub fn load(path: &Path) -> Result<Config> {
+        let text = fs::read_to_string(path)?;
+        Ok(toml::from_str(&text)?)
+    }
+}
@@ -0,0 +1,333 @@
+//! Reading the header of GGUF files, as used by llama.cpp and quantized Flux models
+//!
+//! A GGUF file starts with a block of typed key/value metadata, followed by the name, shape, type
+//! and offset of every tensor, then the tensor data. Only the first two parts are read.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use serde_json::Value;
+
+/// Metadata and tensor list read from a GGUF file, see [`read_gguf_header`]
+#[derive(Clone, Debug, Default)]
+pub struct GgufHeader {
+    /// Metadata values, with strings as they are and everything else as JSON
+    pub metadata: HashMap<String, String>,
+    /// Name, shape and lowercase type name of every tensor, sorted by name
+    ///
+    /// GGUF lists dimensions innermost first, so they're reversed to match safetensors.
+    pub tensors: Vec<(String, Vec<usize>, String)>,
+    /// Size of the whole file
+    pub file_size: u64,
+    /// Bytes taken by the metadata and tensor list, before the tensor data
+    ///
+    /// This includes the padding which aligns the start of the data to `general.alignment`.
+    pub header_size: u64,
+}
+
+/// Read the header of a GGUF file
+///
+/// Headers of `limit` bytes or more are rejected, as for safetensors.
+pub fn read_gguf_header(path: &Path, limit: usize) -> Result<GgufHeader> {
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mut header = parse_gguf_header(BufReader::new(file), limit)?;
+    header.file_size = file_size;
+    Ok(header)
+}
+
+/// Parse a GGUF header from the start of a stream, leaving the file size unset
+pub fn parse_gguf_header(read: impl Read, limit: usize) -> Result<GgufHeader> {
+    let mut read = Reader {
+        read: read.take(limit as u64),
+        limit,
+    };
+    let magic: [u8; 4] = read.array()?;
+    ensure!(&magic == b"GGUF", "Not a GGUF file");
+    let version = u32::from_le_bytes(read.array()?);
+    // Version 1 used 32-bit counts and lengths, and has long since been replaced
+    ensure!(
+        matches!(version, 2 | 3),
+        "Unsupported GGUF version {version}"
+    );
+    let tensor_count = read.u64()?;
+    let value_count = read.u64()?;
+
+    let mut metadata = HashMap::new();
+    for _ in 0..value_count {
+        let key = read.string()?;
+        let kind = read.u32()?;
+        let value = read
+            .value(kind, 0)
+            .with_context(|| format!("Invalid value for {key}"))?;
+        let value = match value {
+            Value::String(text) => text,
+            value => value.to_string(),
+        };
+        metadata.insert(key, value);
+    }
+
+    let mut tensors = Vec::new();
+    for _ in 0..tensor_count {
+        let name = read.string()?;
+        let dims = read.u32()?;
+        ensure!(dims <= 8, "Tensor {name} has {dims} dimensions");
+        let mut shape = (0..dims)
+            .map(|_| Ok(read.u64()?.try_into()?))
+            .collect::<Result<Vec<usize>>>()?;
+        shape.reverse();
+        let kind = ggml_type_name(read.u32()?);
+        // The offset into the data section isn't needed
+        read.u64()?;
+        tensors.push((name, shape, kind));
+    }
+    tensors.sort();
+
+    // The tensor data starts at the next multiple of the alignment after the tensor list
+    let alignment = match metadata.get("general.alignment") {
+        Some(alignment) => alignment
+            .parse::<u64>()
+            .ok()
+            .filter(|alignment| alignment.is_power_of_two())
+            .with_context(|| format!("Invalid alignment {alignment}"))?,
+        None => DEFAULT_ALIGNMENT,
+    };
+    let header_size = (limit as u64 - read.read.limit()).next_multiple_of(alignment);
+
+    Ok(GgufHeader {
+        metadata,
+        tensors,
+        file_size: 0,
+        header_size,
+    })
+}
+
+/// Alignment of the tensor data when the file doesn't set `general.alignment`
+const DEFAULT_ALIGNMENT: u64 = 32;
+
+/// Lowercase name of a GGML tensor type, such as "f16" or "q4_k"
+fn ggml_type_name(kind: u32) -> String {
+    let name = match kind {
+        0 => "f32",
+        1 => "f16",
+        2 => "q4_0",
+        3 => "q4_1",
+        6 => "q5_0",
+        7 => "q5_1",
+        8 => "q8_0",
+        9 => "q8_1",
+        10 => "q2_k",
+        11 => "q3_k",
+        12 => "q4_k",
+        13 => "q5_k",
+        14 => "q6_k",
+        15 => "q8_k",
+        16 => "iq2_xxs",
+        17 => "iq2_xs",
+        18 => "iq3_xxs",
+        19 => "iq1_s",
+        20 => "iq4_nl",
+        21 => "iq3_s",
+        22 => "iq2_s",
+        23 => "iq4_xs",
+        24 => "i8",
+        25 => "i16",
+        26 => "i32",
+        27 => "i64",
+        28 => "f64",
+        29 => "iq1_m",
+        30 => "bf16",
+        kind => return format!("type {kind}"),
+    };
+    name.to_string()
+}
+
+/// Maximum nesting of array values, which real files never come close to
+const MAX_ARRAY_DEPTH: usize = 8;
+
+/// Little-endian reader for the header, limited to the maximum header size
+struct Reader<R> {
+    read: std::io::Take<R>,
+    limit: usize,
+}
+
+impl<R: Read> Reader<R> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut bytes = [0; N];
+        if let Err(e) = self.read.read_exact(&mut bytes) {
+            if self.read.limit() == 0 {
+                bail!("Header is over the {}MB limit", self.limit / 1048576);
+            }
+            return Err(e.into());
+        }
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u64()?;
+        ensure!(
+            len <= self.read.limit(),
+            "String runs past the end of the header"
+        );
+        let mut bytes = vec![0; len as usize];
+        self.read.read_exact(&mut bytes)?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    /// Read a metadata value of the given GGUF type, inside `depth` levels of arrays
+    fn value(&mut self, kind: u32, depth: usize) -> Result<Value> {
+        Ok(match kind {
+            0 => Value::from(u8::from_le_bytes(self.array()?)),
+            1 => Value::from(i8::from_le_bytes(self.array()?)),
+            2 => Value::from(u16::from_le_bytes(self.array()?)),
+            3 => Value::from(i16::from_le_bytes(self.array()?)),
+            4 => Value::from(self.u32()?),
+            5 => Value::from(i32::from_le_bytes(self.array()?)),
+            6 => Value::from(f32::from_le_bytes(self.array()?)),
+            7 => Value::from(self.array::<1>()?[0] != 0),
+            8 => Value::from(self.string()?),
+            9 => {
+                // Arrays of arrays are read recursively, so a crafted file could overflow the stack
+                ensure!(depth < MAX_ARRAY_DEPTH, "Arrays are nested too deeply");
+                let kind = self.u32()?;
+                let len = self.u64()?;
+                // Every element takes at least a byte, which stops a bad length allocating wildly
+                ensure!(
+                    len <= self.read.limit(),
+                    "Array runs past the end of the header"
+                );
+                let values = (0..len)
+                    .map(|_| self.value(kind, depth + 1))
+                    .collect::<Result<Vec<_>>>()?;
+                Value::Array(values)
+            }
+            10 => Value::from(self.u64()?),
+            11 => Value::from(i64::from_le_bytes(self.array()?)),
+            12 => Value::from(f64::from_le_bytes(self.array()?)),
+            kind => bail!("Unknown value type {kind}"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metadata::{
+        LoraData, LoraType, ModelType, NetworkType, ParseOptions, DEFAULT_HEADER_LIMIT,
+    };
+
+    use super::*;
+
+    #[test]
+    fn reads_gguf_headers() {
+        let string =
+            |text: &str| [&(text.len() as u64).to_le_bytes()[..], text.as_bytes()].concat();
+        let mut buffer = b"GGUF".to_vec();
+        buffer.extend(3u32.to_le_bytes());
+        buffer.extend(2u64.to_le_bytes());
+        buffer.extend(2u64.to_le_bytes());
+        buffer.extend(string("general.architecture"));
+        buffer.extend(8u32.to_le_bytes());
+        buffer.extend(string("flux"));
+        buffer.extend(string("lora.alpha"));
+        buffer.extend(6u32.to_le_bytes());
+        buffer.extend(16f32.to_le_bytes());
+        // Dimensions are stored innermost first, and the type is a GGML quantization
+        for (name, kind) in [
+            ("transformer.a.lora_B.weight", 12u32),
+            ("transformer.a.lora_A.weight", 1),
+        ] {
+            buffer.extend(string(name));
+            buffer.extend(2u32.to_le_bytes());
+            buffer.extend(3072u64.to_le_bytes());
+            buffer.extend(16u64.to_le_bytes());
+            buffer.extend(kind.to_le_bytes());
+            buffer.extend(0u64.to_le_bytes());
+        }
+
+        let header = parse_gguf_header(&buffer[..], DEFAULT_HEADER_LIMIT).unwrap();
+        assert_eq!(header.metadata["general.architecture"], "flux");
+        assert_eq!(header.metadata["lora.alpha"], "16.0");
+        assert_eq!(
+            header.header_size,
+            (buffer.len() as u64).next_multiple_of(32)
+        );
+        let data = LoraData::from_gguf(header, &ParseOptions::default());
+        assert_eq!(
+            data.tensors,
+            vec![
+                (
+                    "transformer.a.lora_A.weight".to_string(),
+                    vec![16, 3072],
+                    "f16".to_string()
+                ),
+                (
+                    "transformer.a.lora_B.weight".to_string(),
+                    vec![16, 3072],
+                    "q4_k".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            data.model_types,
+            vec![ModelType::Lora(LoraType::LoRA(NetworkType::Transformer))]
+        );
+        assert_eq!(data.precision(), Some("mixed".to_string()));
+
+        assert!(parse_gguf_header(&buffer[..], 64).is_err());
+    }
+
+    #[test]
+    fn aligns_header_size() {
+        let header = |alignment: Option<u32>| {
+            let mut buffer = b"GGUF".to_vec();
+            buffer.extend(3u32.to_le_bytes());
+            buffer.extend(0u64.to_le_bytes());
+            buffer.extend(u64::from(alignment.is_some()).to_le_bytes());
+            if let Some(alignment) = alignment {
+                let key = "general.alignment";
+                buffer.extend((key.len() as u64).to_le_bytes());
+                buffer.extend(key.as_bytes());
+                buffer.extend(4u32.to_le_bytes());
+                buffer.extend(alignment.to_le_bytes());
+            }
+            parse_gguf_header(&buffer[..], DEFAULT_HEADER_LIMIT).map(|header| header.header_size)
+        };
+        // 24 bytes of counts with no metadata, or 57 with the alignment
+        assert_eq!(header(None).unwrap(), 32);
+        assert_eq!(header(Some(128)).unwrap(), 128);
+        assert_eq!(header(Some(8)).unwrap(), 64);
+        assert!(header(Some(0)).is_err());
+        assert!(header(Some(24)).is_err());
+    }
+
+    #[test]
+    fn rejects_deeply_nested_arrays() {
+        let mut buffer = b"GGUF".to_vec();
+        buffer.extend(3u32.to_le_bytes());
+        buffer.extend(0u64.to_le_bytes());
+        buffer.extend(1u64.to_le_bytes());
+        buffer.extend(1u64.to_le_bytes());
+        buffer.push(b'a');
+        buffer.extend(9u32.to_le_bytes());
+        // Each level is an array holding a single array
+        for _ in 0..1000 {
+            buffer.extend(9u32.to_le_bytes());
+            buffer.extend(1u64.to_le_bytes());
+        }
+        let error = parse_gguf_header(&buffer[..], DEFAULT_HEADER_LIMIT).unwrap_err();
+        assert!(format!("{error:#}").contains("nested too deeply"));
+    }
+}
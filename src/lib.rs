@@ -0,0 +1,22 @@
+//! Parsing and inference for LoRA and other safetensors model metadata
+//!
+//! This is the engine behind the LoRA Explorer app, usable without the GUI by depending on the
+//! crate with `default-features = false`.
+//!
+//! ```no_run
+//! use lora_view::{read_header, LoraData, ParseOptions, DEFAULT_HEADER_LIMIT};
+//!
+//! let buffer = read_header("model.safetensors".as_ref(), DEFAULT_HEADER_LIMIT)?;
+//! let data = LoraData::from_buffer(&buffer, &ParseOptions::default())?;
+//! println!("{:?} based on {:?}", data.model_types, data.base_model);
+//! # anyhow::Ok(())
+//! ```
+
+pub mod gguf;
+pub mod metadata;
+
+pub use gguf::{read_gguf_header, GgufHeader};
+pub use metadata::{
+    read_header, read_zip_header, zip_entries, LoraData, LoraType, ModelType, NetworkType,
+    ParseOptions, TrainingParams, DEFAULT_HEADER_LIMIT,
+};
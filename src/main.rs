@@ -1,28 +1,128 @@
 #![windows_subsystem = "windows"]
 
+use std::path::PathBuf;
+
 use clap::Parser;
 use eframe::egui;
+use lora_view::metadata;
 
 mod app;
-mod metadata;
+mod cache;
+mod config;
+
+/// Name of the app, which also determines where its state is stored
+const APP_NAME: &str = "LoRA Explorer";
+
+/// Attach to the console of the process which started us, so command line modes can report errors
+///
+/// A windows subsystem binary doesn't get a console of its own, so without this anything written
+/// to stderr is lost when started from a terminal. Redirected output works either way.
+#[cfg(windows)]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // Failure just means there's no parent console, which leaves nowhere to report anything
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
 
 #[derive(Parser)]
 struct Args {
-    path: Option<String>,
+    /// Models, archives or folders to open, which are shown together if there's more than one
+    paths: Vec<PathBuf>,
+    /// Config file to read instead of the one in the platform config directory
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Write a CSV summary of the models at the path to this file, instead of opening the window
+    #[arg(long, requires = "paths")]
+    csv: Option<PathBuf>,
+    /// Read model paths from stdin, one per line, and write a JSON summary of each to stdout
+    /// instead of opening the window
+    #[arg(long, conflicts_with_all = ["paths", "csv"])]
+    stdin: bool,
+    /// Include a fingerprint of each model's tensors in the --csv or --stdin summary, which means
+    /// reading every file in full
+    #[arg(long)]
+    fingerprint: bool,
+    /// Show developer diagnostics, such as what each tensor name was recognised as
+    #[arg(long)]
+    debug: bool,
 }
 
 fn main() -> eframe::Result {
     let args = Args::parse();
 
+    // The default config file is optional, but one given with --config has to exist. Either way a
+    // config which can't be read falls back to the defaults, and the error is shown in the window.
+    let config_path = args
+        .config
+        .clone()
+        .or_else(|| config::Config::default_path().filter(|path| path.exists()));
+    let (config, config_error) = match config_path.map(|path| {
+        config::Config::load(&path)
+            .map_err(|e| format!("Couldn't read the config {}: {e}", path.display()))
+    }) {
+        Some(Ok(config)) => (config, None),
+        Some(Err(e)) => (Default::default(), Some(e)),
+        None => (Default::default(), None),
+    };
+
+    let parse_options = metadata::ParseOptions {
+        fold_tag_case: config.fold_tag_case.unwrap_or_default(),
+        normalize_tags: config.normalize_tags.unwrap_or_default(),
+        header_limit: config
+            .header_limit_bytes()
+            .unwrap_or(metadata::DEFAULT_HEADER_LIMIT),
+    };
+    if args.stdin || args.csv.is_some() {
+        attach_console();
+    }
+    if let Some(e) = config_error
+        .as_ref()
+        .filter(|_| args.stdin || args.csv.is_some())
+    {
+        eprintln!("{e}");
+    }
+    if args.stdin {
+        if let Err(e) = app::export_jsonl(parse_options, args.fingerprint) {
+            eprintln!("Failed to write summaries: {e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(out) = &args.csv {
+        if let Err(e) = app::export_report(&args.paths, out, parse_options, args.fingerprint) {
+            eprintln!("Failed to write report {}: {e}", out.display());
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([400.0, 600.0])
             .with_min_inner_size([400.0, 300.0]),
+        // The last window size is saved with the app's state, overriding the default above
+        persist_window: true,
         ..Default::default()
     };
     eframe::run_native(
-        "LoRA Explorer",
+        APP_NAME,
         options,
-        Box::new(|cc| Ok(Box::new(app::App::new(cc, args.path)))),
+        Box::new(|cc| {
+            Ok(Box::new(app::App::new(
+                cc,
+                args.paths,
+                config,
+                config_error,
+                args.debug,
+            )))
+        }),
     )
 }
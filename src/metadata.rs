@@ -1,61 +1,167 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::Display,
-    io::{Read, Seek, SeekFrom},
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
     path::Path,
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
 };
 
-use anyhow::{anyhow, bail, ensure, Result};
-use safetensors::SafeTensors;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use safetensors::{tensor::Metadata, Dtype};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tinyjson::JsonValue;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
+use crate::gguf::GgufHeader;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq, Serialize, Deserialize)]
 pub enum NetworkType {
+    /// UNet where the base model couldn't be determined from tensor shapes
     Unet,
+    Sd15Unet,
+    Sd2Unet,
+    SdxlUnet,
     SdClip,
+    /// SD 2.x text encoder (OpenCLIP, using the penultimate hidden layer)
+    Sd2Clip,
     SdxlClip,
     Transformer,
 }
+impl NetworkType {
+    /// Infer which network a LoRA tensor applies to from its name prefix
+    fn from_tensor_name(name: &str) -> Option<NetworkType> {
+        if name.starts_with("lora_te_") {
+            Some(NetworkType::SdClip)
+        } else if name.starts_with("lora_te1_") || name.starts_with("lora_te2_") {
+            Some(NetworkType::SdxlClip)
+        } else if name.starts_with("transformer.") {
+            Some(NetworkType::Transformer)
+        } else if name.starts_with("lora_unet_") {
+            Some(NetworkType::Unet)
+        } else {
+            None
+        }
+    }
+
+    /// Base model family the network belongs to, if it can be told from the tensors
+    fn family(&self) -> Option<&'static str> {
+        match self {
+            NetworkType::Unet => None,
+            NetworkType::Sd15Unet | NetworkType::SdClip => Some("SD 1.x"),
+            NetworkType::Sd2Unet | NetworkType::Sd2Clip => Some("SD 2.x"),
+            NetworkType::SdxlUnet | NetworkType::SdxlClip => Some("SDXL"),
+            NetworkType::Transformer => Some("Flux"),
+        }
+    }
+
+    pub fn is_text_encoder(&self) -> bool {
+        matches!(
+            self,
+            NetworkType::SdClip | NetworkType::Sd2Clip | NetworkType::SdxlClip
+        )
+    }
+}
 impl Display for NetworkType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
             NetworkType::Unet => "UNet",
+            NetworkType::Sd15Unet => "SD 1.x UNet",
+            NetworkType::Sd2Unet => "SD2 UNet",
+            NetworkType::SdxlUnet => "SDXL UNet",
             NetworkType::SdClip => "SD Clip",
+            NetworkType::Sd2Clip => "SD2 Clip",
             NetworkType::SdxlClip => "SDXL Clip",
             NetworkType::Transformer => "Flux Transformer",
         })
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq, Serialize, Deserialize)]
 pub enum LoraType {
     /// Original LoRA type, representing (m*n) residual matrix as product of (m*r) and (r*n)
     LoRA(NetworkType),
     /// DoRA type - same as LoRA but with an additional weight vector
     DoRA(NetworkType),
+    /// LoRA-FA, which freezes the down (A) matrices at their random initialisation and only trains
+    /// the up (B) matrices
+    LoraFa(NetworkType),
     /// LoHa, representing residual matrix as Hadamard transform
     LoHa(NetworkType),
     /// LoKr, representing residual matrix as Kronecker product
     LoKr(NetworkType),
+    /// LyCORIS "full" mode, storing the complete weight difference rather than low-rank factors
+    Full(NetworkType),
+    /// (IA)^3, scaling activations by learned vectors
+    Ia3(NetworkType),
+    /// Orthogonal fine-tuning, rotating weights by block-diagonal orthogonal matrices
+    Oft(NetworkType),
+    /// Butterfly OFT, building the orthogonal matrix from butterfly factors
+    Boft(NetworkType),
+    /// Generalized LoRA, with separate low-rank terms for weight scaling and bias
+    GLoRA(NetworkType),
+}
+impl LoraType {
+    /// The network the LoRA applies to
+    pub fn network(&self) -> NetworkType {
+        match self {
+            LoraType::LoRA(network)
+            | LoraType::DoRA(network)
+            | LoraType::LoraFa(network)
+            | LoraType::LoHa(network)
+            | LoraType::LoKr(network)
+            | LoraType::Full(network)
+            | LoraType::Ia3(network)
+            | LoraType::Oft(network)
+            | LoraType::Boft(network)
+            | LoraType::GLoRA(network) => *network,
+        }
+    }
+
+    fn with_network(self, network: NetworkType) -> LoraType {
+        match self {
+            LoraType::LoRA(_) => LoraType::LoRA(network),
+            LoraType::DoRA(_) => LoraType::DoRA(network),
+            LoraType::LoraFa(_) => LoraType::LoraFa(network),
+            LoraType::LoHa(_) => LoraType::LoHa(network),
+            LoraType::LoKr(_) => LoraType::LoKr(network),
+            LoraType::Full(_) => LoraType::Full(network),
+            LoraType::Ia3(_) => LoraType::Ia3(network),
+            LoraType::Oft(_) => LoraType::Oft(network),
+            LoraType::Boft(_) => LoraType::Boft(network),
+            LoraType::GLoRA(_) => LoraType::GLoRA(network),
+        }
+    }
 }
 impl Display for LoraType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&match self {
             LoraType::LoRA(network) => format!("{network} LoRA"),
             LoraType::DoRA(network) => format!("{network} DoRA"),
+            LoraType::LoraFa(network) => format!("{network} LoRA-FA"),
             LoraType::LoHa(network) => format!("{network} LoHa"),
             LoraType::LoKr(network) => format!("{network} LoKr"),
+            LoraType::Full(network) => format!("{network} full fine-tune (no rank)"),
+            LoraType::Ia3(network) => format!("{network} IA3"),
+            LoraType::Oft(network) => format!("{network} OFT"),
+            LoraType::Boft(network) => format!("{network} BOFT"),
+            LoraType::GLoRA(network) => format!("{network} GLoRA"),
         })
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialOrd, PartialEq, Serialize, Deserialize)]
 pub enum ModelType {
     SdCheckpoint,
     SdxlCheckpoint,
+    Sd3Checkpoint,
+    FluxCheckpoint,
     Lora(LoraType),
     BakedVae,
     StandaloneVae,
+    /// Textual inversion embedding
+    Embedding,
 }
 impl ModelType {
     /// Attempt to infer model type from a tensor name
@@ -71,6 +177,16 @@ impl ModelType {
             return Some(ModelType::SdCheckpoint);
         }
 
+        // SD3 and Flux LoRAs use the same block names, so require the full checkpoint prefix
+        if name.starts_with("model.diffusion_model.joint_blocks.") {
+            return Some(ModelType::Sd3Checkpoint);
+        }
+        if name.starts_with("model.diffusion_model.double_blocks.")
+            || name.starts_with("model.diffusion_model.single_blocks.")
+        {
+            return Some(ModelType::FluxCheckpoint);
+        }
+
         // Standalone and baked-in VAEs have easily recognized model names
         if name.starts_with("encoder.") {
             return Some(ModelType::StandaloneVae);
@@ -79,18 +195,14 @@ impl ModelType {
             return Some(ModelType::BakedVae);
         }
 
+        // Embeddings store their vectors under a handful of fixed names
+        if matches!(name, "emb_params" | "clip_l" | "clip_g") || name.starts_with("string_to_param")
+        {
+            return Some(ModelType::Embedding);
+        }
+
         // All remaining model types we recognize are some kind of LoRA
-        let model = if name.starts_with("lora_te_") {
-            NetworkType::SdClip
-        } else if name.starts_with("lora_te1_") {
-            NetworkType::SdxlClip
-        } else if name.starts_with("transformer.") {
-            NetworkType::Transformer
-        } else if name.starts_with("lora_unet_") {
-            NetworkType::Unet
-        } else {
-            return None;
-        };
+        let model = NetworkType::from_tensor_name(name)?;
 
         // All remaining model types we recognize are some kind of LoRA, so find the subtype first
         let lora_type = if name.ends_with("lora_down.weight")
@@ -105,6 +217,14 @@ impl ModelType {
             LoraType::LoKr(model)
         } else if name.ends_with("dora_scale") {
             LoraType::DoRA(model)
+        } else if name.ends_with(".diff") || name.ends_with(".diff_b") {
+            LoraType::Full(model)
+        } else if name.ends_with(".on_input") {
+            LoraType::Ia3(model)
+        } else if name.ends_with(".oft_blocks") || name.ends_with(".oft_diag") {
+            LoraType::Oft(model)
+        } else if name.ends_with(".a1.weight") || name.ends_with(".b1.weight") {
+            LoraType::GLoRA(model)
         } else {
             return None;
         };
@@ -117,75 +237,717 @@ impl Display for ModelType {
         f.write_str(&match self {
             ModelType::SdCheckpoint => "SD Checkpoint".to_string(),
             ModelType::SdxlCheckpoint => "SDXL Checkpoint".to_string(),
+            ModelType::Sd3Checkpoint => "SD3 Checkpoint".to_string(),
+            ModelType::FluxCheckpoint => "Flux Checkpoint".to_string(),
             ModelType::Lora(lora) => lora.to_string(),
             ModelType::BakedVae => "Baked-in VAE".to_string(),
             ModelType::StandaloneVae => "Standalone VAE".to_string(),
+            ModelType::Embedding => "Textual Inversion Embedding".to_string(),
         })
     }
 }
 
-#[derive(Default)]
+/// Fields from the Stability AI model spec (`modelspec.*` keys), written by many non-kohya tools
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelSpec {
+    pub architecture: Option<String>,
+    pub resolution: Option<String>,
+    pub implementation: Option<String>,
+}
+impl ModelSpec {
+    fn from_metadata(metadata: &HashMap<String, String>) -> ModelSpec {
+        ModelSpec {
+            architecture: metadata_value(metadata, "modelspec.architecture"),
+            resolution: metadata_value(metadata, "modelspec.resolution"),
+            implementation: metadata_value(metadata, "modelspec.implementation"),
+        }
+    }
+}
+
+/// Options controlling how a model is read and its metadata interpreted
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParseOptions {
+    /// Merge tags which only differ by case, summing their frequencies
+    pub fold_tag_case: bool,
+    /// Merge tags which match after [`normalize_tag`], such as `Long_Hair` and `long hair`
+    pub normalize_tags: bool,
+    /// Size in bytes from which headers are rejected as implausibly large
+    pub header_limit: usize,
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            fold_tag_case: false,
+            normalize_tags: false,
+            header_limit: DEFAULT_HEADER_LIMIT,
+        }
+    }
+}
+
+/// A dataset directory used in training, from `ss_dataset_dirs` or `ss_reg_dataset_dirs`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DatasetDir {
+    pub name: String,
+    pub repeats: Option<u32>,
+    pub images: Option<u64>,
+}
+
+/// Read a kohya-style dataset directory listing, sorted by directory name
+fn dataset_dirs(metadata: &HashMap<String, String>, key: &str) -> Vec<DatasetDir> {
+    let Some(JsonValue::Object(dirs)) = metadata.get(key).and_then(|v| v.parse::<JsonValue>().ok())
+    else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<_> = dirs
+        .iter()
+        .map(|(name, info)| DatasetDir {
+            name: name.to_string(),
+            repeats: json_number(info, "n_repeats").map(|n| n as u32),
+            images: json_number(info, "img_count").map(|n| n as u64),
+        })
+        .collect();
+    dirs.sort_by(|a, b| a.name.cmp(&b.name));
+    dirs
+}
+
+/// A resolution bucket used in training, from `ss_bucket_info`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bucket {
+    pub width: u32,
+    pub height: u32,
+    pub images: u64,
+}
+
+/// Read kohya-style bucket info, sorted from tallest to widest aspect ratio
+fn buckets(metadata: &HashMap<String, String>) -> Vec<Bucket> {
+    let Some(JsonValue::Object(info)) = metadata
+        .get("ss_bucket_info")
+        .and_then(|v| v.parse::<JsonValue>().ok())
+    else {
+        return Vec::new();
+    };
+    let Some(JsonValue::Object(buckets)) = info.get("buckets") else {
+        return Vec::new();
+    };
+    let mut buckets: Vec<_> = buckets
+        .values()
+        .filter_map(|bucket| {
+            let JsonValue::Object(fields) = bucket else {
+                return None;
+            };
+            let JsonValue::Array(resolution) = fields.get("resolution")? else {
+                return None;
+            };
+            let [JsonValue::Number(width), JsonValue::Number(height)] = resolution.as_slice()
+            else {
+                return None;
+            };
+            Some(Bucket {
+                width: *width as u32,
+                height: *height as u32,
+                images: json_number(bucket, "count")? as u64,
+            })
+        })
+        .collect();
+    buckets.sort_by(|a, b| {
+        let ratio = |bucket: &Bucket| bucket.width as f64 / bucket.height.max(1) as f64;
+        ratio(a).total_cmp(&ratio(b))
+    });
+    buckets
+}
+
+/// Read the keyword arguments given to the network module, from `ss_network_args`
+///
+/// kohya writes these as a JSON object, usually with every value as a string, but some tools
+/// write a Python dict instead, which is converted with [`python_literal_to_json`]. Values which
+/// aren't strings are formatted as JSON.
+fn network_args(metadata: &HashMap<String, String>) -> BTreeMap<String, String> {
+    let Some(args) = metadata.get("ss_network_args") else {
+        return BTreeMap::new();
+    };
+    let Some(JsonValue::Object(args)) = args
+        .parse::<JsonValue>()
+        .or_else(|_| python_literal_to_json(args).parse())
+        .ok()
+    else {
+        return BTreeMap::new();
+    };
+    args.iter()
+        .map(|(key, value)| {
+            let value = match value {
+                JsonValue::String(text) => text.clone(),
+                value => value.stringify().unwrap_or_default(),
+            };
+            (key.clone(), value)
+        })
+        .collect()
+}
+
+/// Rewrite a Python literal, such as the repr of a dict, as JSON
+///
+/// Strings in either kind of quotes become JSON strings, `True`, `False` and `None` become their
+/// JSON equivalents and tuples become arrays. Everything else is passed through unchanged, so
+/// the result may still not be valid JSON.
+fn python_literal_to_json(text: &str) -> String {
+    let mut json = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                let mut value = String::new();
+                while let Some(next) = chars.next() {
+                    match next {
+                        '\\' => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some('r') => value.push('\r'),
+                            escaped => value.extend(escaped),
+                        },
+                        next if next == c => break,
+                        next => value.push(next),
+                    }
+                }
+                json.push_str(&JsonValue::String(value).stringify().unwrap_or_default());
+            }
+            '(' => json.push('['),
+            ')' => json.push(']'),
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    word.push(next);
+                }
+                json.push_str(match word.as_str() {
+                    "True" => "true",
+                    "False" => "false",
+                    "None" => "null",
+                    word => word,
+                });
+            }
+            c => json.push(c),
+        }
+    }
+    json
+}
+
+fn json_number(value: &JsonValue, key: &str) -> Option<f64> {
+    let JsonValue::Object(object) = value else {
+        return None;
+    };
+    match object.get(key)? {
+        JsonValue::Number(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Training settings recorded in kohya-style metadata
+///
+/// Every field is optional, since the keys present depend on the trainer and its version.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrainingParams {
+    pub dataset_dirs: Vec<DatasetDir>,
+    pub reg_dataset_dirs: Vec<DatasetDir>,
+    pub train_images: Option<u64>,
+    pub reg_images: Option<u64>,
+    pub caption_dropout_rate: Option<f64>,
+    pub caption_dropout_every_n_epochs: Option<u32>,
+    pub caption_tag_dropout_rate: Option<f64>,
+    pub shuffle_caption: Option<bool>,
+    pub keep_tokens: Option<u32>,
+    pub buckets: Vec<Bucket>,
+    pub learning_rate: Option<f64>,
+    pub unet_lr: Option<f64>,
+    pub text_encoder_lr: Option<f64>,
+    pub lr_scheduler: Option<String>,
+    pub optimizer: Option<String>,
+    pub epochs: Option<u32>,
+    pub max_train_steps: Option<u64>,
+    pub clip_skip: Option<u32>,
+    pub noise_offset: Option<f64>,
+    pub min_snr_gamma: Option<f64>,
+    pub mixed_precision: Option<String>,
+}
+impl TrainingParams {
+    pub fn from_metadata(metadata: &HashMap<String, String>) -> TrainingParams {
+        let reg_dataset_dirs = dataset_dirs(metadata, "ss_reg_dataset_dirs");
+        let reg_images: Option<u64> = metadata_value(metadata, "ss_num_reg_images").or_else(|| {
+            (!reg_dataset_dirs.is_empty())
+                .then(|| reg_dataset_dirs.iter().filter_map(|dir| dir.images).sum())
+        });
+        TrainingParams {
+            dataset_dirs: dataset_dirs(metadata, "ss_dataset_dirs"),
+            reg_dataset_dirs,
+            train_images: metadata_value(metadata, "ss_num_train_images"),
+            reg_images,
+            caption_dropout_rate: metadata_value(metadata, "ss_caption_dropout_rate"),
+            caption_dropout_every_n_epochs: metadata_value(
+                metadata,
+                "ss_caption_dropout_every_n_epochs",
+            ),
+            caption_tag_dropout_rate: metadata_value(metadata, "ss_caption_tag_dropout_rate"),
+            shuffle_caption: metadata_value::<String>(metadata, "ss_shuffle_caption")
+                .and_then(|v| parse_bool(&v)),
+            keep_tokens: metadata_value(metadata, "ss_keep_tokens"),
+            buckets: buckets(metadata),
+            learning_rate: metadata_value(metadata, "ss_learning_rate"),
+            unet_lr: metadata_value(metadata, "ss_unet_lr"),
+            text_encoder_lr: metadata_value(metadata, "ss_text_encoder_lr"),
+            lr_scheduler: metadata_value(metadata, "ss_lr_scheduler"),
+            optimizer: metadata_value(metadata, "ss_optimizer"),
+            epochs: metadata_value(metadata, "ss_num_epochs"),
+            max_train_steps: metadata_value(metadata, "ss_max_train_steps"),
+            clip_skip: metadata_value(metadata, "ss_clip_skip"),
+            noise_offset: metadata_value(metadata, "ss_noise_offset"),
+            min_snr_gamma: metadata_value(metadata, "ss_min_snr_gamma"),
+            mixed_precision: metadata_value(metadata, "ss_mixed_precision"),
+        }
+    }
+
+    /// Number of distinct training images, which is what tag frequencies are counted against
+    ///
+    /// This is the sum of the dataset directory image counts, since `ss_num_train_images` also
+    /// counts repeats. It's only used as a fallback when no directory has an image count.
+    pub fn image_count(&self) -> Option<u64> {
+        let mut counts = self
+            .dataset_dirs
+            .iter()
+            .filter_map(|dir| dir.images)
+            .peekable();
+        let count = if counts.peek().is_some() {
+            Some(counts.sum())
+        } else {
+            self.train_images
+        };
+        count.filter(|&count| count > 0)
+    }
+
+    /// Label and formatted value of every setting that is present, in display order
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        fn push(
+            entries: &mut Vec<(&'static str, String)>,
+            label: &'static str,
+            value: Option<impl Display>,
+        ) {
+            if let Some(value) = value {
+                entries.push((label, value.to_string()));
+            }
+        }
+        let mut entries = Vec::new();
+        push(&mut entries, "Learning rate", self.learning_rate);
+        push(&mut entries, "UNet learning rate", self.unet_lr);
+        push(
+            &mut entries,
+            "Text encoder learning rate",
+            self.text_encoder_lr,
+        );
+        push(&mut entries, "LR scheduler", self.lr_scheduler.as_ref());
+        push(&mut entries, "Optimizer", self.optimizer.as_ref());
+        push(&mut entries, "Epochs", self.epochs);
+        push(&mut entries, "Max train steps", self.max_train_steps);
+        push(&mut entries, "Clip skip", self.clip_skip);
+        push(&mut entries, "Noise offset", self.noise_offset);
+        push(&mut entries, "Min SNR gamma", self.min_snr_gamma);
+        push(
+            &mut entries,
+            "Mixed precision",
+            self.mixed_precision.as_ref(),
+        );
+        push(&mut entries, "Training images", self.train_images);
+        push(&mut entries, "Regularization images", self.reg_images);
+        push(&mut entries, "Shuffle captions", self.shuffle_caption);
+        push(&mut entries, "Keep tokens", self.keep_tokens);
+        push(
+            &mut entries,
+            "Caption dropout rate",
+            self.caption_dropout_rate,
+        );
+        push(
+            &mut entries,
+            "Caption dropout every n epochs",
+            self.caption_dropout_every_n_epochs,
+        );
+        push(
+            &mut entries,
+            "Tag dropout rate",
+            self.caption_tag_dropout_rate,
+        );
+        entries
+    }
+}
+
+/// Parse a metadata value, treating missing, empty and "None" values as absent
+fn metadata_value<T: FromStr>(metadata: &HashMap<String, String>, key: &str) -> Option<T> {
+    metadata
+        .get(key)
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty() && *v != "None")
+        .and_then(|v| v.parse().ok())
+}
+
+/// Parse a boolean as written by Python, which capitalises "True" and "False"
+fn parse_bool(value: &str) -> Option<bool> {
+    if value.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LoraData {
     pub raw_metadata: HashMap<String, String>,
     pub tag_frequencies: Vec<(String, f64)>,
+    /// Tag frequencies of each dataset directory, sorted by directory name
+    pub dir_tag_frequencies: Vec<(String, Vec<(String, f64)>)>,
     pub base_model: Option<String>,
-    pub tensors: Vec<(String, Vec<usize>)>,
+    /// Display title, from `modelspec.title`
+    pub title: Option<String>,
+    pub model_spec: ModelSpec,
+    /// Trigger phrase for the model, from `modelspec.trigger_phrase` or `ss_training_comment`
+    pub activation_text: Option<String>,
+    /// Name, shape and lowercase dtype name of every tensor, sorted by name
+    pub tensors: Vec<(String, Vec<usize>, String)>,
     pub model_types: Vec<ModelType>,
+    /// Network rank recorded by the trainer in `ss_network_dim`
+    pub network_dim: Option<u32>,
+    /// Network alpha from `ss_network_alpha`, which may be an integer or fractional string
+    pub network_alpha: Option<f64>,
+    /// Size of the whole model file in bytes
+    pub file_size: u64,
+    /// Bytes of the file taken by the header, with the metadata and tensor list, before the data
+    pub header_size: u64,
+    pub training: TrainingParams,
+    /// Keyword arguments of the network module from `ss_network_args`, such as LyCORIS settings
+    pub network_args: BTreeMap<String, String>,
+    /// Reason the file couldn't be parsed, if loading failed
+    pub error: Option<String>,
 }
 
-/// Read the header of a safetensors file, padding to the full model size
+/// Default limit on the size of a safetensors header we're willing to parse
+///
+/// As with the real safetensors implementation, the header size is restricted to 100MB, although
+/// we're a little more generous in allowing 100*2^20 instead of 100*10^6.
+pub const DEFAULT_HEADER_LIMIT: usize = 100 * 1048576;
+
+/// Read the header of a safetensors file
 ///
 /// This is a convenience function for reading the header of a safetensors file when we know we
-/// aren't going to need the tensor weights themselves. It only reads the header (as defined by the
-/// first 8 bytes of the file), returning a buffer where all remaining data is zero. As with the
-/// real safetensors implementation, the header size is restricted to 100MB, although we're a
-/// little more generous in allowing 100*2^20 instead of 100*10^6.
-///
-/// This is clearly not very memory efficient, but allows us to use the proper safetensors
-/// validation, which needs the buffer to be the correct size. Its main purpose is to speed up
-/// processing of very large model files, for example when accidentally trying to load a checkpoint
-/// instead of a LoRA.
-pub fn read_header(path: &Path) -> Result<Vec<u8>> {
-    let mut read = std::fs::File::open(path)?;
-    let model_size: usize = read.metadata()?.len().try_into()?;
-
-    let mut size: [u8; 8] = [0; 8];
-    read.read_exact(&mut size)?;
-    let size: usize = u64::from_le_bytes(size).try_into()?;
+/// aren't going to need the tensor weights themselves. Only the header size and the JSON header
+/// are read, which [`LoraData::from_buffer`] can parse without the tensor data. This keeps scanning
+/// a folder of large files (for example when accidentally loading checkpoints instead of LoRAs)
+/// quick and light on memory.
+///
+/// Headers of `limit` bytes or more are rejected, see [`DEFAULT_HEADER_LIMIT`].
+pub fn read_header(path: &Path, limit: usize) -> Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut start = Vec::new();
+    (&mut file).take(9).read_to_end(&mut start)?;
+    check_format(path, &start)?;
+    file.rewind()?;
+    read_header_from(BufReader::new(file), limit)
+}
+
+/// Whether a path has an extension, ignoring case
+///
+/// Files like `Model.SafeTensors` are common, so extensions are never matched exactly.
+pub fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// Extensions of safetensors files, the full `.safetensors` and the short `.sft`
+const SAFETENSORS_EXTENSIONS: [&str; 2] = ["safetensors", "sft"];
+
+/// Extensions of every file the viewer can open: safetensors, GGUF and zip archives of models
+const SUPPORTED_EXTENSIONS: [&str; 4] = [
+    SAFETENSORS_EXTENSIONS[0],
+    SAFETENSORS_EXTENSIONS[1],
+    "gguf",
+    "zip",
+];
+
+/// Whether a path has a safetensors extension, either `.safetensors` or the short `.sft`
+pub fn has_safetensors_extension(path: &Path) -> bool {
+    SAFETENSORS_EXTENSIONS
+        .iter()
+        .any(|extension| has_extension(path, extension))
+}
+
+/// Check the start of a file looks like safetensors, with a helpful error if it doesn't
+///
+/// Other model formats are recognised by their extension or magic bytes, so the error can say
+/// what the file is rather than failing to parse it as a header.
+fn check_format(path: &Path, start: &[u8]) -> Result<()> {
+    // The header is a JSON object straight after its size
+    if start.get(8) == Some(&b'{') {
+        return Ok(());
+    }
+    if start.starts_with(b"GGUF") {
+        bail!("This is a GGUF file, which doesn't have a safetensors header");
+    }
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if !has_safetensors_extension(path) => {
+            let supported: Vec<_> = SUPPORTED_EXTENSIONS
+                .iter()
+                .map(|extension| format!(".{extension}"))
+                .collect();
+            bail!(
+                "Unsupported format (.{ext}) \u{2014} only {} are supported",
+                supported.join(", ")
+            )
+        }
+        _ => match start {
+            [b'P', b'K', 3, 4, ..] => {
+                bail!("Not a safetensors file, it looks like a zipped PyTorch checkpoint")
+            }
+            [0x80, 2..=5, ..] => bail!("Not a safetensors file, it looks like a pickle"),
+            _ => bail!("Not a safetensors file"),
+        },
+    }
+}
+
+/// Check a header size read from the first 8 bytes of a file, returning the size with those bytes
+fn check_header_size(size: u64, limit: usize) -> Result<usize> {
+    let size: usize = size.try_into()?;
     let size = size.checked_add(8).ok_or(anyhow!("Invalid header size"))?;
-    ensure!(size < 100 * 1048576);
+    ensure!(
+        size < limit,
+        "Header is {} bytes, over the {}MB limit",
+        size - 8,
+        limit / 1048576
+    );
+    Ok(size)
+}
 
-    let mut buffer = Vec::with_capacity(model_size);
+/// Read the header of a safetensors stream, stopping before the tensor data
+///
+/// The returned buffer holds the 8 byte header size followed by the JSON header, as for
+/// [`read_header`], which works for any source, such as compressed entries in an archive.
+pub fn read_header_from(mut read: impl Read, limit: usize) -> Result<Vec<u8>> {
+    let mut size_bytes: [u8; 8] = [0; 8];
+    read.read_exact(&mut size_bytes)?;
+    let size = check_header_size(u64::from_le_bytes(size_bytes), limit)?;
+
+    let mut buffer = Vec::with_capacity(size);
+    buffer.extend_from_slice(&size_bytes);
     buffer.resize(size, 0);
-    read.seek(SeekFrom::Start(0))?;
-    read.read_exact(&mut buffer)?;
-    buffer.resize(model_size, 0);
+    read.read_exact(&mut buffer[8..])?;
     Ok(buffer)
 }
 
-impl LoraData {
-    pub fn from_buffer(buffer: &[u8]) -> Result<LoraData> {
-        let metadata = SafeTensors::read_metadata(buffer)?.1;
-        let metadata: HashMap<_, _> = metadata
-            .metadata()
-            .as_ref()
-            .map(|m| m.iter().collect())
-            .unwrap_or_default();
+/// The JSON header at the start of a buffer returned by [`read_header`]
+///
+/// Anything after the header is ignored, so this also works on a whole model file.
+pub fn header_bytes(buffer: &[u8]) -> Result<&[u8]> {
+    let size_bytes: [u8; 8] = buffer
+        .get(..8)
+        .ok_or(anyhow!("File too short for a safetensors header"))?
+        .try_into()?;
+    let size: usize = u64::from_le_bytes(size_bytes).try_into()?;
+    size.checked_add(8)
+        .and_then(|end| buffer.get(8..end))
+        .ok_or(anyhow!("Truncated header"))
+}
+
+/// List the safetensors files contained in a zip archive, sorted by name
+pub fn zip_entries(path: &Path) -> Result<Vec<String>> {
+    let archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut names: Vec<_> = archive
+        .file_names()
+        .filter(|name| has_safetensors_extension(Path::new(name)))
+        .map(|name| name.to_string())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Read the header of a safetensors file stored in a zip archive
+///
+/// The entry is only decompressed as far as the end of its header, see [`read_header_from`].
+pub fn read_zip_header(path: &Path, entry: &str, limit: usize) -> Result<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let file = archive.by_name(entry)?;
+    read_header_from(file, limit)
+}
+
+/// Compute a fingerprint of the tensor content of a safetensors file
+///
+/// The fingerprint is a SHA-256 hash over the name, dtype, shape and data of every tensor, taken
+/// in name order. The free-form metadata header is deliberately left out, so two files which only
+/// differ in their metadata (a re-upload with an edited description, say) share a fingerprint.
+/// Unlike [`read_header`], this needs to read the entire file, although it's streamed through the
+/// hash rather than held in memory. `progress` is updated with the number of bytes hashed so far.
+pub fn content_fingerprint(path: &Path, progress: &AtomicU64) -> Result<String> {
+    // The whole file is read anyway, so there's no point limiting the header size
+    let buffer = read_header(path, usize::MAX)?;
+    let header = header_bytes(&buffer)?;
+    let parsed: Metadata = serde_json::from_slice(header).context("Invalid safetensors header")?;
+    let mut infos: Vec<_> = parsed.tensors().into_iter().collect();
+    infos.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-        let all_tags = tag_frequencies(&metadata).ok().unwrap_or_default();
+    let data_start = 8 + header.len() as u64;
+    let mut file = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0; 1 << 20];
+    for (name, info) in infos {
+        let dtype = format!("{:?}", info.dtype);
+        let (start, end) = info.data_offsets;
+        ensure!(start <= end, "Invalid data offsets for {name}");
+        // Length-prefix the variable sized fields so different tensor sets can't collide
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update((dtype.len() as u64).to_le_bytes());
+        hasher.update(dtype.as_bytes());
+        hasher.update((info.shape.len() as u64).to_le_bytes());
+        for dim in &info.shape {
+            hasher.update((*dim as u64).to_le_bytes());
+        }
+        let mut remaining = (end - start) as u64;
+        hasher.update(remaining.to_le_bytes());
+        file.seek(SeekFrom::Start(data_start + start as u64))?;
+        while remaining > 0 {
+            let wanted = remaining.min(chunk.len() as u64) as usize;
+            file.read_exact(&mut chunk[..wanted])
+                .with_context(|| format!("Missing data for {name}"))?;
+            hasher.update(&chunk[..wanted]);
+            progress.fetch_add(wanted as u64, Ordering::Relaxed);
+            remaining -= wanted as u64;
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Compute the SHA-256 hash of everything after the header of a safetensors file
+///
+/// This is the hash kohya's scripts store in `sshs_model_hash`, so can be used to check the tensor
+/// data hasn't been corrupted. `progress` is updated with the number of bytes hashed so far.
+pub fn data_sha256(path: &Path, progress: &AtomicU64) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut size = [0; 8];
+    file.read_exact(&mut size)?;
+    // The whole file is read anyway, so there's no point limiting the header size
+    let header_size = check_header_size(u64::from_le_bytes(size), usize::MAX)?;
+    file.seek(SeekFrom::Start(header_size as u64))?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        progress.fetch_add(read as u64, Ordering::Relaxed);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+impl LoraData {
+    /// Interpret a safetensors header, as returned by [`read_header`]
+    ///
+    /// Only the header is parsed, so the buffer doesn't need the tensor data after it, although it
+    /// can be a whole model file. [`safetensors::SafeTensors::read_metadata`] insists on the data
+    /// being there, so the header is deserialized directly, and the file size comes from the tensor
+    /// data offsets.
+    pub fn from_buffer(buffer: &[u8], options: &ParseOptions) -> Result<LoraData> {
+        let header = header_bytes(buffer)?;
+        let parsed: Metadata =
+            serde_json::from_slice(header).context("Invalid safetensors header")?;
+        let metadata: HashMap<String, String> = parsed.metadata().clone().unwrap_or_default();
 
-        let tensors = SafeTensors::deserialize(buffer)?;
-        let mut names = tensors.names();
-        names.sort();
-        let tensors: Result<Vec<_>> = names
+        let infos = parsed.tensors();
+        let mut tensors: Vec<_> = infos
             .iter()
-            .map(|name| Ok((name.to_string(), tensors.tensor(name)?.shape().to_vec())))
+            .map(|(name, info)| (name.clone(), info.shape.clone(), dtype_name(info.dtype)))
             .collect();
-        let tensors = tensors.unwrap_or_default();
+        tensors.sort();
+        let data_len = infos
+            .values()
+            .map(|info| info.data_offsets.1)
+            .max()
+            .unwrap_or(0);
+        let header_size = 8 + header.len() as u64;
+        let file_size = header_size + data_len as u64;
 
+        Ok(LoraData::from_parts(
+            metadata,
+            tensors,
+            header_size,
+            file_size,
+            options,
+        ))
+    }
+
+    /// Interpret the header of a GGUF file, see [`crate::gguf::read_gguf_header`]
+    pub fn from_gguf(header: GgufHeader, options: &ParseOptions) -> LoraData {
+        LoraData::from_parts(
+            header.metadata,
+            header.tensors,
+            header.header_size,
+            header.file_size,
+            options,
+        )
+    }
+
+    /// Work out everything we show about a model from its metadata and tensor list
+    fn from_parts(
+        metadata: HashMap<String, String>,
+        tensors: Vec<(String, Vec<usize>, String)>,
+        header_size: u64,
+        file_size: u64,
+        options: &ParseOptions,
+    ) -> LoraData {
+        let dir_tags = dir_tag_frequencies(&metadata, options)
+            .ok()
+            .unwrap_or_default();
+        let all_tags = merge_tag_frequencies(&dir_tags);
+
+        // SD 2.x LoRAs use the same names as SD 1.x, but kohya flags them in the metadata
+        let sd2 =
+            metadata_value::<String>(&metadata, "ss_v2").and_then(|v| parse_bool(&v)) == Some(true);
+        let unet = if sd2 {
+            NetworkType::Sd2Unet
+        } else {
+            unet_network(&tensors)
+        };
+        // The network module (and for LyCORIS, the algorithm in its arguments) disambiguates
+        // algorithms whose tensor names overlap
+        let module_hint = ["ss_network_module", "ss_network_args"]
+            .iter()
+            .filter_map(|key| metadata.get(*key))
+            .map(|value| value.to_lowercase())
+            .collect::<Vec<_>>()
+            .join(" ");
         let mut model_types: HashSet<_> = tensors
             .iter()
-            .filter_map(|(name, shape)| ModelType::from_tensor_name(name, shape))
+            .filter_map(|(name, shape, _)| {
+                ModelType::from_tensor_name(name, shape).or_else(|| {
+                    // IA3 vectors are just called "weight", which is too generic to go on alone
+                    let network = NetworkType::from_tensor_name(name)?;
+                    (module_hint.contains("ia3") && name.ends_with(".weight") && shape.len() == 1)
+                        .then_some(ModelType::Lora(LoraType::Ia3(network)))
+                })
+            })
+            .map(|t| match t {
+                ModelType::Lora(LoraType::Oft(network)) if module_hint.contains("boft") => {
+                    ModelType::Lora(LoraType::Boft(network))
+                }
+                t => t,
+            })
+            .map(|t| match t {
+                ModelType::Lora(lora) => ModelType::Lora(lora.with_network(match lora.network() {
+                    NetworkType::Unet => unet,
+                    NetworkType::SdClip if sd2 => NetworkType::Sd2Clip,
+                    network => network,
+                })),
+                t => t,
+            })
             .collect();
 
         // DoRA has a strict superset of the tensors in a standard LoRA. If we've detected
@@ -200,45 +962,1427 @@ impl LoraData {
         dora_types.into_iter().for_each(|t| {
             model_types.remove(&ModelType::Lora(LoraType::LoRA(t)));
         });
-        let mut model_types: Vec<_> = model_types.into_iter().collect();
+
+        // LoRA-FA stores the same tensors as a plain LoRA, with the down (A) matrices frozen rather
+        // than missing, so only kohya's lora_fa module gives it away. Rank stabilization is
+        // orthogonal to this, see [`LoraData::rank_stabilized`].
+        let args = network_args(&metadata);
+        let lora_fa = metadata
+            .get("ss_network_module")
+            .is_some_and(|module| module.to_lowercase().contains("lora_fa"));
+        let mut model_types: Vec<_> = model_types
+            .into_iter()
+            .map(|t| match t {
+                ModelType::Lora(LoraType::LoRA(network)) if lora_fa => {
+                    ModelType::Lora(LoraType::LoraFa(network))
+                }
+                t => t,
+            })
+            .collect();
         model_types.sort();
 
-        Ok(LoraData {
-            raw_metadata: metadata
+        // Full weight differences don't have a rank, so ignore any dimension the trainer recorded
+        // rather than showing a misleading value
+        let full_only = !model_types.is_empty()
+            && model_types
                 .iter()
-                .map(|(k, v)| (k.to_string(), v.to_string()))
-                .collect(),
+                .all(|t| matches!(t, ModelType::Lora(LoraType::Full(_))));
+
+        let model_spec = ModelSpec::from_metadata(&metadata);
+        LoraData {
+            base_model: known_base_model(&metadata)
+                .map(str::to_string)
+                .or_else(|| metadata.get("ss_sd_model_name").cloned())
+                .or_else(|| {
+                    metadata
+                        .get("ss_base_model_version")
+                        .map(|version| base_model_version_name(version))
+                })
+                .or_else(|| model_spec.architecture.as_deref().map(architecture_name)),
+            title: metadata_value(&metadata, "modelspec.title"),
+            model_spec,
+            activation_text: metadata_value(&metadata, "modelspec.trigger_phrase")
+                .or_else(|| metadata_value(&metadata, "ss_training_comment")),
+            network_dim: metadata
+                .get("ss_network_dim")
+                .and_then(|dim| dim.parse().ok())
+                .filter(|_| !full_only),
+            network_alpha: metadata
+                .get("ss_network_alpha")
+                .and_then(|alpha| alpha.parse().ok()),
+            file_size,
+            header_size,
+            training: TrainingParams::from_metadata(&metadata),
+            network_args: args,
+            raw_metadata: metadata,
             tag_frequencies: all_tags,
-            base_model: metadata
-                .get(&"ss_sd_model_name".to_string())
-                .map(|s| s.to_string()),
+            dir_tag_frequencies: dir_tags,
             tensors,
             model_types,
+            error: None,
+        }
+    }
+
+    /// Total number of tensor elements in the model
+    pub fn parameter_count(&self) -> u64 {
+        self.tensors
+            .iter()
+            .map(|(_, shape, _)| shape.iter().product::<usize>() as u64)
+            .sum()
+    }
+
+    /// SHA-256 hash of the tensor data recorded in the metadata, as lowercase hex
+    ///
+    /// See [`data_sha256`] for how this is calculated.
+    pub fn stored_sha256(&self) -> Option<String> {
+        ["sshs_model_hash", "modelspec.hash_sha256"]
+            .iter()
+            .find_map(|key| self.raw_metadata.get(*key))
+            .map(|hash| hash.trim().trim_start_matches("0x").to_lowercase())
+            .filter(|hash| !hash.is_empty())
+    }
+
+    /// Dtype shared by all tensors, or "mixed" if they differ
+    ///
+    /// Returns None if there are no tensors.
+    pub fn precision(&self) -> Option<String> {
+        let (_, _, first) = self.tensors.first()?;
+        if self.tensors.iter().all(|(_, _, dtype)| dtype == first) {
+            Some(first.clone())
+        } else {
+            Some("mixed".to_string())
+        }
+    }
+
+    /// Best guess at the base model family from the detected networks, for files without metadata
+    ///
+    /// Returns None if the networks don't identify a family, or identify more than one.
+    pub fn inferred_base(&self) -> Option<String> {
+        let families: HashSet<_> = self
+            .model_types
+            .iter()
+            .filter_map(|model_type| match model_type {
+                ModelType::Lora(lora) => lora.network().family(),
+                _ => None,
+            })
+            .collect();
+        match Vec::from_iter(families).as_slice() {
+            [family] => Some(format!("{family}-based")),
+            _ => None,
+        }
+    }
+
+    /// Number of token vectors stored in a textual inversion embedding
+    pub fn embedding_vectors(&self) -> Option<usize> {
+        let (_, shape, _) = self.tensors.iter().find(|(name, shape, _)| {
+            ModelType::from_tensor_name(name, shape) == Some(ModelType::Embedding)
+        })?;
+        Some(if shape.len() >= 2 { shape[0] } else { 1 })
+    }
+
+    /// Total number of tensor elements belonging to each network the LoRA applies to
+    pub fn parameters_by_network(&self) -> HashMap<NetworkType, u64> {
+        let mut counts = HashMap::new();
+        for (name, shape, _) in &self.tensors {
+            if let Some(network) = NetworkType::from_tensor_name(name) {
+                *counts.entry(network).or_default() += shape.iter().product::<usize>() as u64;
+            }
+        }
+        counts
+    }
+
+    /// Number of tensors belonging to each network the LoRA applies to
+    pub fn tensors_by_network(&self) -> HashMap<NetworkType, usize> {
+        let mut counts = HashMap::new();
+        for (name, _, _) in &self.tensors {
+            if let Some(network) = NetworkType::from_tensor_name(name) {
+                *counts.entry(network).or_default() += 1;
+            }
+        }
+        counts
+    }
+
+    /// Whether the LoRA has any text encoder weights, rather than only training the UNet
+    pub fn trains_text_encoder(&self) -> bool {
+        self.tensors.iter().any(|(name, _, _)| {
+            NetworkType::from_tensor_name(name).is_some_and(|network| network.is_text_encoder())
+        })
+    }
+
+    /// Whether the file has any UNet weights, as opposed to only text encoder or transformer ones
+    pub fn has_unet(&self) -> bool {
+        self.tensors
+            .iter()
+            .any(|(name, _, _)| name.starts_with("lora_unet_"))
+    }
+
+    /// Whether the UNet's convolution layers are trained as well as attention, as in LoCon
+    pub fn has_conv_layers(&self) -> bool {
+        self.tensors.iter().any(|(name, _, _)| {
+            is_conv_module(name)
+                && (name.ends_with("lora_down.weight")
+                    || name.ends_with("hada_w1_a")
+                    || name.ends_with("lokr_w1"))
+        })
+    }
+
+    /// Rank of the convolution layers, from `conv_dim` in the network args or else the tensors
+    pub fn conv_dim(&self) -> Option<u32> {
+        if !self.has_conv_layers() {
+            return None;
+        }
+        self.network_arg("conv_dim").or_else(|| {
+            self.tensors
+                .iter()
+                .filter(|(name, _, _)| is_conv_module(name) && name.ends_with("lora_down.weight"))
+                .filter_map(|(_, shape, _)| shape.first().map(|&rank| rank as u32))
+                .max()
         })
     }
+
+    /// Alpha of the convolution layers, from `conv_alpha` in the network args
+    pub fn conv_alpha(&self) -> Option<f64> {
+        self.has_conv_layers()
+            .then(|| self.network_arg("conv_alpha"))
+            .flatten()
+    }
+
+    /// Whether this is a rank-stabilized LoRA, scaled by alpha/sqrt(rank) rather than alpha/rank
+    ///
+    /// This is a network argument, since the tensors are the same as those of a plain LoRA.
+    pub fn rank_stabilized(&self) -> bool {
+        ["rank_stabilized", "rs_lora", "use_rslora"]
+            .iter()
+            .any(|key| self.network_args.get(*key).and_then(|v| parse_bool(v)) == Some(true))
+    }
+
+    /// Parse a value from the network args, treating empty and "None" values as absent
+    fn network_arg<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.network_args
+            .get(key)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty() && *v != "None")
+            .and_then(|v| v.parse().ok())
+    }
+
+    /// Ratio of text encoder parameters to UNet (or transformer) parameters
+    ///
+    /// Returns None if the LoRA doesn't contain any UNet weights.
+    pub fn text_encoder_ratio(&self) -> Option<f64> {
+        let (te, unet) = self.parameters_by_network().into_iter().fold(
+            (0, 0),
+            |(te, unet), (network, count)| {
+                if network.is_text_encoder() {
+                    (te + count, unet)
+                } else {
+                    (te, unet + count)
+                }
+            },
+        );
+        (unet > 0).then(|| te as f64 / unet as f64)
+    }
+
+    /// Minimum and maximum rank of the LoRA decomposition tensors
+    ///
+    /// The rank is the inner dimension, which is the first dimension of down (A) tensors and the
+    /// second of up (B) tensors. This can vary between layers, in which case `ss_network_dim`
+    /// doesn't tell the whole story.
+    pub fn rank_range(&self) -> Option<(usize, usize)> {
+        self.tensors
+            .iter()
+            .filter_map(|(name, shape, _)| {
+                if name.ends_with("lora_down.weight") || name.ends_with("lora_A.weight") {
+                    shape.first().copied()
+                } else if name.ends_with("lora_up.weight") || name.ends_with("lora_B.weight") {
+                    shape.get(1).copied()
+                } else {
+                    None
+                }
+            })
+            .fold(None, |range, rank| match range {
+                None => Some((rank, rank)),
+                Some((min, max)) => Some((min.min(rank), max.max(rank))),
+            })
+    }
+
+    /// Signs of a broken or mis-exported LoRA, found by checking the tensors against the metadata
+    ///
+    /// Only shapes are read, so per-layer alpha values can't be compared, but missing alpha
+    /// tensors are reported since most tools then use alpha = rank, ignoring `ss_network_alpha`.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let downs: Vec<_> = self
+            .tensors
+            .iter()
+            .filter(|(name, shape, _)| {
+                (name.ends_with("lora_down.weight") || name.ends_with("lora_A.weight"))
+                    && shape.len() >= 2
+            })
+            .collect();
+        if downs.is_empty() {
+            return warnings;
+        }
+
+        if let (Some(dim), Some((min, max))) = (self.network_dim, self.rank_range()) {
+            if min == max && min != dim as usize {
+                warnings.push(format!(
+                    "The metadata gives rank {dim}, but the tensors have rank {min}"
+                ));
+            }
+        }
+        let has_alpha = self
+            .tensors
+            .iter()
+            .any(|(name, _, _)| name.ends_with(".alpha"));
+        if let Some(alpha) = self.network_alpha.filter(|_| !has_alpha) {
+            warnings.push(format!(
+                "There are no per-layer alpha tensors, so most tools will ignore the metadata's \
+                 alpha of {alpha} and apply the LoRA at full strength"
+            ));
+        }
+
+        // Down tensors are (rank, input, ...), so a rank above the input size can't help. Rank 1
+        // layers are only suspicious if the trainer wasn't asked for rank 1.
+        let degenerate: Vec<_> = downs
+            .iter()
+            .filter(|(_, shape, _)| shape[0] == 1)
+            .filter(|_| self.network_dim != Some(1))
+            .collect();
+        if let Some((name, _, _)) = degenerate.first() {
+            warnings.push(format!(
+                "{} layer(s) have rank 1, such as {name}",
+                degenerate.len()
+            ));
+        }
+        let oversized: Vec<_> = downs
+            .iter()
+            .filter(|(_, shape, _)| shape[0] > shape[1])
+            .collect();
+        if let Some((name, shape, _)) = oversized.first() {
+            warnings.push(format!(
+                "{} layer(s) have a rank larger than their input size, such as {name} \
+                 (rank {}, input {})",
+                oversized.len(),
+                shape[0],
+                shape[1]
+            ));
+        }
+        warnings
+    }
+
+    /// Model type each tensor name suggests on its own, in tensor order
+    ///
+    /// This is the first step in working out [`LoraData::model_types`], before the metadata and
+    /// the other tensors refine it, so it shows which tensors drive the result and which aren't
+    /// recognised at all.
+    pub fn tensor_types(&self) -> Vec<(&str, Option<ModelType>)> {
+        self.tensors
+            .iter()
+            .map(|(name, shape, _)| (name.as_str(), ModelType::from_tensor_name(name, shape)))
+            .collect()
+    }
+
+    /// Most common prefix of the tensor names, with the number of tensors which have it
+    ///
+    /// This is to help identify formats which aren't recognised, see [`tensor_prefix`].
+    pub fn common_tensor_prefix(&self) -> Option<(&str, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (name, _, _) in &self.tensors {
+            *counts.entry(tensor_prefix(name)).or_default() += 1;
+        }
+        counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+    }
+
+    /// Whether the file loaded but neither a model type nor a rank could be determined
+    pub fn is_unrecognized(&self) -> bool {
+        self.error.is_none()
+            && self.model_types.is_empty()
+            && self.network_dim.is_none()
+            && self.rank_range().is_none()
+    }
+}
+
+/// Whether a LoRA tensor applies to one of the UNet's convolution modules
+///
+/// Kohya names these after the `conv1`, `conv2` and `conv_shortcut` layers of the resnets, the
+/// `conv` of the up and down samplers and `conv_in`/`conv_out`, so the module name has to end in
+/// one of them; attention layers can contain "conv" elsewhere in the name.
+fn is_conv_module(name: &str) -> bool {
+    const CONV_MODULES: [&str; 6] = [
+        "_conv1",
+        "_conv2",
+        "_conv_shortcut",
+        "_conv",
+        "_conv_in",
+        "_conv_out",
+    ];
+    let module = name.split('.').next().unwrap_or(name);
+    module.starts_with("lora_unet_") && CONV_MODULES.iter().any(|suffix| module.ends_with(suffix))
 }
 
-fn tag_frequencies(metadata: &HashMap<&String, &String>) -> Result<Vec<(String, f64)>> {
+/// Start of a tensor name which is likely to be shared by the whole model or one of its parts
+///
+/// This is the first dot-separated component, cut down to its first two words if it's a
+/// kohya-style name with underscores, such as `lora_unet` or `transformer`.
+fn tensor_prefix(name: &str) -> &str {
+    let first = name.split('.').next().unwrap_or(name);
+    match first.match_indices('_').nth(1) {
+        Some((end, _)) => &first[..end],
+        None => first,
+    }
+}
+
+/// Work out which UNet a LoRA applies to from its cross-attention shapes
+///
+/// The cross-attention key and value projections take the text encoder output as input, which is
+/// 768 wide for SD 1.x and 2048 (CLIP-L and CLIP-G concatenated) for SDXL. This shows up as the
+/// input dimension of the down (A) matrices. If there are no such tensors, or they disagree, the
+/// generic [`NetworkType::Unet`] is returned.
+fn unet_network(tensors: &[(String, Vec<usize>, String)]) -> NetworkType {
+    let context_dims: HashSet<usize> = tensors
+        .iter()
+        .filter(|(name, _, _)| {
+            name.starts_with("lora_unet_")
+                && (name.contains("attn2_to_k") || name.contains("attn2_to_v"))
+                && (name.ends_with("lora_down.weight") || name.ends_with("lora_A.weight"))
+        })
+        .filter_map(|(_, shape, _)| shape.get(1).copied())
+        .collect();
+    match Vec::from_iter(context_dims).as_slice() {
+        [768] => NetworkType::Sd15Unet,
+        [2048] => NetworkType::SdxlUnet,
+        _ => NetworkType::Unet,
+    }
+}
+
+/// Lowercase name of a dtype, such as "bf16" or "f8_e4m3"
+fn dtype_name(dtype: Dtype) -> String {
+    format!("{dtype:?}").to_lowercase()
+}
+
+/// Well-known base checkpoints by AutoV2 hash, the first 10 hex digits of the file's SHA-256
+const AUTOV2_BASE_MODELS: &[(&str, &str)] = &[
+    ("fe4efff1e1", "SD 1.4"),
+    ("cc6cb27103", "SD 1.5"),
+    ("6ce0161689", "SD 1.5"),
+    ("1a189f0be6", "SD 1.5"),
+    ("ad2a33c361", "SD 2.1 768"),
+    ("31e35c80fc", "SDXL 1.0 Base"),
+    ("7440042bbd", "SDXL 1.0 Refiner"),
+    ("67ab2fd8ec", "Pony Diffusion V6"),
+];
+
+/// Well-known base checkpoints by the legacy 8 digit hash of a small part of the file
+const LEGACY_BASE_MODELS: &[(&str, &str)] = &[
+    ("7460a6fa", "SD 1.4"),
+    ("81761151", "SD 1.5"),
+    ("e1441589", "SD 1.5"),
+    ("925997e9", "NovelAI"),
+];
+
+/// Look up the base checkpoint's hashes from `ss_new_sd_model_hash` or `ss_sd_model_hash`
+///
+/// Checkpoint filenames are often renamed, so a recognised hash is a better guide to what the
+/// model was trained on.
+fn known_base_model(metadata: &HashMap<String, String>) -> Option<&'static str> {
+    let lookup = |key: &str, table: &[(&str, &'static str)], len: usize| {
+        let hash = metadata.get(key)?.trim().to_lowercase();
+        let hash = hash.get(..len)?;
+        table
+            .iter()
+            .find(|(known, _)| *known == hash)
+            .map(|(_, name)| *name)
+    };
+    lookup("ss_new_sd_model_hash", AUTOV2_BASE_MODELS, 10)
+        .or_else(|| lookup("ss_sd_model_hash", LEGACY_BASE_MODELS, 8))
+}
+
+/// Map a `ss_base_model_version` string to a friendly base model name
+///
+/// Newer trainers record the base model family this way rather than (or as well as) by checkpoint
+/// filename. Unrecognised versions are returned as-is.
+fn base_model_version_name(version: &str) -> String {
+    let name = match version {
+        "sd_v1" => "SD 1.x",
+        v if v.starts_with("sd_v2") => "SD 2.x",
+        v if v.starts_with("sdxl_base") => "SDXL 1.0",
+        v if v.starts_with("sd3") => "SD3",
+        v if v.starts_with("flux1") => "Flux.1",
+        _ => return version.to_string(),
+    };
+    name.to_string()
+}
+
+/// Map a `modelspec.architecture` string to a friendly base model name
+///
+/// Architectures look like `stable-diffusion-xl-v1-base/lora`, with the part after the slash
+/// giving the kind of model. Unrecognised architectures are returned as-is.
+fn architecture_name(architecture: &str) -> String {
+    let base = architecture.split('/').next().unwrap_or(architecture);
+    let name = match base {
+        a if a.starts_with("stable-diffusion-xl") => "SDXL 1.0",
+        a if a.starts_with("stable-diffusion-v1") => "SD 1.x",
+        a if a.starts_with("stable-diffusion-v2") => "SD 2.x",
+        a if a.starts_with("stable-diffusion-v3") => "SD3",
+        a if a.starts_with("flux-1") => "Flux.1",
+        _ => return architecture.to_string(),
+    };
+    name.to_string()
+}
+
+/// Read the tag frequencies of each dataset directory in a metadata map
+///
+/// Tags are read from the kohya-style `ss_tag_frequency` entry, a JSON object mapping each dataset
+/// directory to an object of tag counts. Directories are sorted by name and their tags by
+/// descending frequency. If `fold_tag_case` is set in `options`, tags are lowercased and merged,
+/// and if `normalize_tags` is set they're merged by [`normalize_tag`] instead.
+/// Fails if the entry is missing or doesn't have the expected structure.
+pub fn dir_tag_frequencies(
+    metadata: &HashMap<String, String>,
+    options: &ParseOptions,
+) -> Result<Vec<(String, Vec<(String, f64)>)>> {
     let frequencies = metadata
-        .get(&"ss_tag_frequency".to_string())
+        .get("ss_tag_frequency")
         .ok_or(anyhow!("Could not get tag frequencies"))?;
-    let mut all_tags = HashMap::new();
-    let frequencies: JsonValue = frequencies.parse().unwrap();
+    let frequencies: JsonValue = frequencies
+        .parse()
+        .map_err(|e| anyhow!("Invalid tag frequency json: {e}"))?;
     let JsonValue::Object(dirs) = frequencies else {
         bail!("Unexpected json structure")
     };
+    // Other tools write this slightly differently, so skip anything malformed rather than losing
+    // every tag over one bad entry
+    let mut result = Vec::new();
     for dir in dirs.iter() {
         let JsonValue::Object(tags) = dir.1 else {
-            bail!("Unexpected json structure")
+            continue;
         };
+        let mut dir_tags = HashMap::new();
         for tag in tags {
-            all_tags
-                .entry(tag.0.to_string())
-                .and_modify(|v| *v += tag.1.get::<f64>().unwrap())
-                .or_insert(*tag.1.get::<f64>().unwrap());
+            let count = match tag.1 {
+                JsonValue::Number(count) => *count,
+                JsonValue::String(count) => match count.trim().parse() {
+                    Ok(count) => count,
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            let tag = if options.normalize_tags {
+                normalize_tag(tag.0)
+            } else if options.fold_tag_case {
+                tag.0.to_lowercase()
+            } else {
+                tag.0.to_string()
+            };
+            *dir_tags.entry(tag).or_insert(0.0) += count;
+        }
+        let mut dir_tags: Vec<_> = dir_tags.into_iter().collect();
+        dir_tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+        result.push((dir.0.to_string(), dir_tags));
+    }
+    result.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(result)
+}
+
+/// Normalise a caption tag for merging, lowercasing it and using spaces between words
+///
+/// Booru-style captions write `long_hair` where others write `long hair`, so underscores are
+/// turned into spaces, and runs of them collapse into one. Surrounding whitespace is removed.
+pub fn normalize_tag(tag: &str) -> String {
+    let words: Vec<_> = tag
+        .split(|c: char| c == '_' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect();
+    words.join(" ").to_lowercase()
+}
+
+/// Merge per-directory tag frequencies, summing the counts for each tag
+///
+/// The result is sorted by descending frequency.
+pub fn merge_tag_frequencies(dirs: &[(String, Vec<(String, f64)>)]) -> Vec<(String, f64)> {
+    let mut all_tags = HashMap::new();
+    for (_, tags) in dirs {
+        for (tag, count) in tags {
+            *all_tags.entry(tag.clone()).or_insert(0.0) += count;
         }
     }
     let mut all_tags: Vec<_> = all_tags.into_iter().collect();
-    all_tags.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-    Ok(all_tags)
+    all_tags.sort_by(|a, b| b.1.total_cmp(&a.1));
+    all_tags
+}
+
+#[cfg(test)]
+mod tests {
+    use safetensors::{serialize, tensor::TensorView};
+
+    use super::*;
+
+    /// Serialize a model with zeroed F32 tensors of the given names and shapes
+    fn model_buffer(tensors: &[(&str, &[usize])], metadata: &[(&str, &str)]) -> Vec<u8> {
+        let data: Vec<Vec<u8>> = tensors
+            .iter()
+            .map(|(_, shape)| vec![0; shape.iter().product::<usize>() * 4])
+            .collect();
+        let views = tensors.iter().zip(&data).map(|((name, shape), data)| {
+            (
+                name.to_string(),
+                TensorView::new(Dtype::F32, shape.to_vec(), data).unwrap(),
+            )
+        });
+        let metadata = metadata
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        serialize(views, &Some(metadata)).unwrap()
+    }
+
+    /// Parse a model built by [`model_buffer`] with the default options
+    fn parse(tensors: &[(&str, &[usize])], metadata: &[(&str, &str)]) -> LoraData {
+        LoraData::from_buffer(&model_buffer(tensors, metadata), &ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn detects_full_fine_tune() {
+        let data = parse(
+            &[
+                ("lora_unet_down_blocks_0_attentions_0_proj_in.diff", &[4, 4]),
+                ("lora_unet_down_blocks_0_attentions_0_proj_in.diff_b", &[4]),
+                ("lora_te_text_model_encoder_layers_0_mlp_fc1.diff", &[4, 4]),
+            ],
+            &[("ss_network_dim", "16")],
+        );
+        assert_eq!(
+            data.model_types,
+            vec![
+                ModelType::Lora(LoraType::Full(NetworkType::Unet)),
+                ModelType::Lora(LoraType::Full(NetworkType::SdClip)),
+            ]
+        );
+        assert_eq!(data.network_dim, None);
+    }
+
+    #[test]
+    fn detects_embeddings() {
+        for names in [
+            &["emb_params"][..],
+            &["string_to_param.*"],
+            &["clip_g", "clip_l"],
+        ] {
+            let tensors: Vec<(&str, &[usize])> = names
+                .iter()
+                .map(|name| (*name, &[2, 768] as &[usize]))
+                .collect();
+            let data = parse(&tensors, &[]);
+            assert_eq!(data.model_types, vec![ModelType::Embedding], "{names:?}");
+            assert_eq!(data.embedding_vectors(), Some(2), "{names:?}");
+        }
+    }
+
+    #[test]
+    fn parses_bucket_info() {
+        let info = r#"{"buckets": {"0": {"resolution": [768, 512], "count": 10},
+            "1": {"resolution": [512, 768], "count": 30}, "2": {"resolution": [640, 640]}},
+            "mean_img_ar_error": 0.01}"#;
+        let metadata = HashMap::from([("ss_bucket_info".to_string(), info.to_string())]);
+        let training = TrainingParams::from_metadata(&metadata);
+        assert_eq!(
+            training.buckets,
+            vec![
+                Bucket {
+                    width: 512,
+                    height: 768,
+                    images: 30
+                },
+                Bucket {
+                    width: 768,
+                    height: 512,
+                    images: 10
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_sd3_and_flux_checkpoints() {
+        let detect = |name| ModelType::from_tensor_name(name, &[]);
+        assert_eq!(
+            detect("model.diffusion_model.joint_blocks.0.x_block.attn.qkv.weight"),
+            Some(ModelType::Sd3Checkpoint)
+        );
+        assert_eq!(
+            detect("model.diffusion_model.double_blocks.0.img_attn.qkv.weight"),
+            Some(ModelType::FluxCheckpoint)
+        );
+        assert_eq!(
+            detect("model.diffusion_model.single_blocks.0.linear1.weight"),
+            Some(ModelType::FluxCheckpoint)
+        );
+        // LoRA tensors for the same blocks aren't checkpoints
+        assert_eq!(
+            detect("joint_blocks.0.x_block.attn.qkv.lora_A.weight"),
+            None
+        );
+        assert_eq!(
+            detect("text_encoders.clip_l.transformer.text_model.final_layer_norm.weight"),
+            None
+        );
+    }
+
+    #[test]
+    fn distinguishes_unet_by_context_dim() {
+        let network = |context_dim: usize| {
+            let shape = [4, context_dim];
+            parse(
+                &[
+                    (
+                        "lora_unet_input_blocks_4_1_transformer_blocks_0_attn2_to_k.lora_down.weight",
+                        &shape,
+                    ),
+                    (
+                        "lora_unet_input_blocks_4_1_transformer_blocks_0_attn2_to_k.lora_up.weight",
+                        &[320, 4],
+                    ),
+                ],
+                &[],
+            )
+                .model_types
+        };
+        assert_eq!(
+            network(768),
+            vec![ModelType::Lora(LoraType::LoRA(NetworkType::Sd15Unet))]
+        );
+        assert_eq!(
+            network(2048),
+            vec![ModelType::Lora(LoraType::LoRA(NetworkType::SdxlUnet))]
+        );
+        // SD 2.x has a different context size again, so isn't guessed
+        assert_eq!(
+            network(1024),
+            vec![ModelType::Lora(LoraType::LoRA(NetworkType::Unet))]
+        );
+    }
+
+    #[test]
+    fn measures_rank_range() {
+        let data = parse(
+            &[
+                ("lora_unet_a.lora_down.weight", &[8, 320]),
+                ("lora_unet_a.lora_up.weight", &[320, 8]),
+                ("lora_unet_b.lora_down.weight", &[32, 64, 3, 3]),
+                ("lora_unet_b.lora_up.weight", &[64, 32, 1, 1]),
+            ],
+            &[("ss_network_dim", "16")],
+        );
+        assert_eq!(data.rank_range(), Some((8, 32)));
+        assert_eq!(data.network_dim, Some(16));
+    }
+
+    #[test]
+    fn reads_rank_and_alpha() {
+        let load = |metadata: &[(&str, &str)]| {
+            let data = parse(&[("lora_unet_a.lora_down.weight", &[4, 8])], metadata);
+            (data.network_dim, data.network_alpha)
+        };
+        assert_eq!(
+            load(&[("ss_network_dim", "32"), ("ss_network_alpha", "16")]),
+            (Some(32), Some(16.0))
+        );
+        assert_eq!(load(&[("ss_network_alpha", "0.5")]), (None, Some(0.5)));
+        assert_eq!(load(&[("ss_network_dim", "None")]), (None, None));
+        assert_eq!(load(&[]), (None, None));
+    }
+
+    #[test]
+    fn prefers_modelspec_trigger_phrase() {
+        let options = ParseOptions::default();
+        let comment = [("ss_training_comment", "sks dog")];
+        let buffer = model_buffer(&[], &comment);
+        let data = LoraData::from_buffer(&buffer, &options).unwrap();
+        assert_eq!(data.activation_text.as_deref(), Some("sks dog"));
+
+        let both = [comment[0], ("modelspec.trigger_phrase", "photo of sks")];
+        let buffer = model_buffer(&[], &both);
+        let data = LoraData::from_buffer(&buffer, &options).unwrap();
+        assert_eq!(data.activation_text.as_deref(), Some("photo of sks"));
+    }
+
+    #[test]
+    fn falls_back_to_modelspec_architecture() {
+        let data = parse(
+            &[],
+            &[
+                ("modelspec.architecture", "stable-diffusion-xl-v1-base/lora"),
+                ("modelspec.title", "My LoRA"),
+            ],
+        );
+        assert_eq!(data.base_model.as_deref(), Some("SDXL 1.0"));
+        assert_eq!(data.title.as_deref(), Some("My LoRA"));
+    }
+
+    #[test]
+    fn promotes_sd2_from_metadata() {
+        let tensors: [(&str, &[usize]); 2] = [
+            (
+                "lora_unet_mid_block_attentions_0_proj_in.lora_down.weight",
+                &[4, 1280],
+            ),
+            (
+                "lora_te_text_model_encoder_layers_0_mlp_fc1.lora_down.weight",
+                &[4, 1024],
+            ),
+        ];
+        let model_types = |v2| parse(&tensors, &[("ss_v2", v2)]).model_types;
+        assert_eq!(
+            model_types("True"),
+            vec![
+                ModelType::Lora(LoraType::LoRA(NetworkType::Sd2Unet)),
+                ModelType::Lora(LoraType::LoRA(NetworkType::Sd2Clip)),
+            ]
+        );
+        assert_eq!(
+            model_types("False"),
+            vec![
+                ModelType::Lora(LoraType::LoRA(NetworkType::Unet)),
+                ModelType::Lora(LoraType::LoRA(NetworkType::SdClip)),
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_other_lycoris_algorithms() {
+        let model_types = |tensors: &[(&str, &[usize])], metadata: &[(&str, &str)]| {
+            parse(tensors, metadata).model_types
+        };
+        let unet =
+            |lora: fn(NetworkType) -> LoraType| vec![ModelType::Lora(lora(NetworkType::Unet))];
+
+        assert_eq!(
+            model_types(&[("lora_unet_a.oft_blocks", &[4, 8, 8])], &[]),
+            unet(LoraType::Oft)
+        );
+        assert_eq!(
+            model_types(
+                &[("lora_unet_a.oft_blocks", &[4, 8, 8])],
+                &[("ss_network_args", r#"{"algo": "boft"}"#)]
+            ),
+            unet(LoraType::Boft)
+        );
+        assert_eq!(
+            model_types(
+                &[
+                    ("lora_unet_a.a1.weight", &[4, 8]),
+                    ("lora_unet_a.b1.weight", &[8, 4])
+                ],
+                &[]
+            ),
+            unet(LoraType::GLoRA)
+        );
+        // A bare weight vector is only IA3 if the metadata says so
+        let ia3: [(&str, &[usize]); 1] = [("lora_unet_a.weight", &[8])];
+        assert!(model_types(&ia3, &[]).is_empty());
+        assert_eq!(
+            model_types(&ia3, &[("ss_network_args", r#"{"algo": "ia3"}"#)]),
+            unet(LoraType::Ia3)
+        );
+    }
+
+    #[test]
+    fn reports_tensor_precision() {
+        let data = parse(&[("lora_unet_a.lora_down.weight", &[4, 8])], &[]);
+        assert_eq!(data.tensors[0].2, "f32");
+        assert_eq!(data.precision().as_deref(), Some("f32"));
+        assert_eq!(dtype_name(Dtype::BF16), "bf16");
+    }
+
+    #[test]
+    fn counts_distinct_training_images() {
+        let training = |metadata: &[(&str, &str)]| {
+            let metadata = metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            TrainingParams::from_metadata(&metadata)
+        };
+        let dirs = r#"{"10_cat": {"n_repeats": 10, "img_count": 40}, "5_dog": {"n_repeats": 5, "img_count": 8}}"#;
+        assert_eq!(
+            training(&[("ss_dataset_dirs", dirs), ("ss_num_train_images", "440")]).image_count(),
+            Some(48)
+        );
+        assert_eq!(
+            training(&[("ss_num_train_images", "440")]).image_count(),
+            Some(440)
+        );
+        assert_eq!(
+            training(&[("ss_num_train_images", "0")]).image_count(),
+            None
+        );
+    }
+
+    #[test]
+    fn keeps_tags_per_directory() {
+        let metadata = [(
+            "ss_tag_frequency".to_string(),
+            r#"{"5_dog": {"dog": 3, "Grass": 1}, "10_cat": {"cat": 4, "grass": 2}}"#.to_string(),
+        )]
+        .into_iter()
+        .collect();
+        let options = ParseOptions {
+            fold_tag_case: true,
+            ..Default::default()
+        };
+        let dirs = dir_tag_frequencies(&metadata, &options).unwrap();
+        assert_eq!(
+            dirs,
+            vec![
+                (
+                    "10_cat".to_string(),
+                    vec![("cat".to_string(), 4.0), ("grass".to_string(), 2.0)]
+                ),
+                (
+                    "5_dog".to_string(),
+                    vec![("dog".to_string(), 3.0), ("grass".to_string(), 1.0)]
+                ),
+            ]
+        );
+        let merged = merge_tag_frequencies(&dirs);
+        assert_eq!(merged[0], ("cat".to_string(), 4.0));
+        assert!(merged.contains(&("grass".to_string(), 3.0)));
+    }
+
+    #[test]
+    fn hashes_tensor_data() {
+        let buffer = model_buffer(
+            &[("lora_unet_a.lora_down.weight", &[4, 8])],
+            &[("sshs_model_hash", "0xABC")],
+        );
+        let path = std::env::temp_dir().join("lora_view_hashes_tensor_data.safetensors");
+        std::fs::write(&path, &buffer).unwrap();
+        let progress = AtomicU64::new(0);
+        let hash = data_sha256(&path, &progress);
+        std::fs::remove_file(&path).ok();
+        let header_size = 8 + u64::from_le_bytes(buffer[..8].try_into().unwrap()) as usize;
+        let expected = format!("{:x}", Sha256::digest(&buffer[header_size..]));
+        assert_eq!(hash.unwrap(), expected);
+        assert_eq!(progress.into_inner(), 4 * 8 * 4);
+
+        let data = LoraData::from_buffer(&buffer, &ParseOptions::default()).unwrap();
+        assert_eq!(data.stored_sha256().as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn fingerprint_ignores_metadata() {
+        let fingerprint = |tensors: &[(&str, &[usize])], metadata: &[(&str, &str)]| {
+            let path =
+                std::env::temp_dir().join("lora_view_fingerprint_ignores_metadata.safetensors");
+            std::fs::write(&path, model_buffer(tensors, metadata)).unwrap();
+            let progress = AtomicU64::new(0);
+            let fingerprint = content_fingerprint(&path, &progress).unwrap();
+            std::fs::remove_file(&path).ok();
+            (fingerprint, progress.into_inner())
+        };
+        let tensors: &[(&str, &[usize])] = &[
+            ("lora_unet_a.lora_down.weight", &[4, 8]),
+            ("lora_unet_a.lora_up.weight", &[8, 4]),
+        ];
+        let (plain, progress) = fingerprint(tensors, &[]);
+        assert_eq!(progress, 2 * 4 * 8 * 4);
+        assert_eq!(
+            fingerprint(tensors, &[("ss_output_name", "edited")]).0,
+            plain
+        );
+        let reshaped: &[(&str, &[usize])] = &[
+            ("lora_unet_a.lora_down.weight", &[8, 4]),
+            ("lora_unet_a.lora_up.weight", &[8, 4]),
+        ];
+        assert_ne!(fingerprint(reshaped, &[]).0, plain);
+    }
+
+    #[test]
+    fn names_base_model_from_hash() {
+        let base_model = |metadata: &[(&str, &str)]| {
+            parse(&[("lora_unet_a.lora_down.weight", &[4, 8])], metadata).base_model
+        };
+        let sdxl = "31e35c80fc4829d14f90153f4c74cd59c90b779f6afe05a74cd6120b893f7e5b";
+        assert_eq!(
+            base_model(&[
+                ("ss_sd_model_name", "my_base.safetensors"),
+                ("ss_new_sd_model_hash", sdxl)
+            ])
+            .as_deref(),
+            Some("SDXL 1.0 Base")
+        );
+        assert_eq!(
+            base_model(&[("ss_sd_model_hash", "925997e9")]).as_deref(),
+            Some("NovelAI")
+        );
+        assert_eq!(
+            base_model(&[
+                ("ss_sd_model_name", "my_base.safetensors"),
+                ("ss_sd_model_hash", "00000000")
+            ])
+            .as_deref(),
+            Some("my_base.safetensors")
+        );
+    }
+
+    #[test]
+    fn reads_hyperparameters() {
+        let metadata = [
+            ("ss_learning_rate", "0.0001"),
+            ("ss_optimizer", "bitsandbytes.optim.adamw.AdamW8bit"),
+            ("ss_num_epochs", "10"),
+            ("ss_clip_skip", "None"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let training = TrainingParams::from_metadata(&metadata);
+        assert_eq!(training.learning_rate, Some(0.0001));
+        assert_eq!(training.epochs, Some(10));
+        assert_eq!(training.clip_skip, None);
+        let entries = training.entries();
+        assert_eq!(entries[0], ("Learning rate", "0.0001".to_string()));
+        assert!(entries.contains(&(
+            "Optimizer",
+            "bitsandbytes.optim.adamw.AdamW8bit".to_string()
+        )));
+    }
+
+    #[test]
+    fn explains_unsupported_formats() {
+        let error = |path: &str, start: &[u8]| {
+            check_format(Path::new(path), start)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(check_format(Path::new("a.safetensors"), b"\x02\0\0\0\0\0\0\0{}").is_ok());
+        assert_eq!(
+            error("model.ckpt", b"PK\x03\x04"),
+            "Unsupported format (.ckpt) \u{2014} only .safetensors, .sft, .gguf, .zip are supported"
+        );
+        assert!(error("model.safetensors", b"\x80\x02}q").contains("pickle"));
+        assert_eq!(error("model.safetensors", b""), "Not a safetensors file");
+        assert!(error("Model.SafeTensors", b"").starts_with("Not a safetensors"));
+        assert!(error("model.sft", b"").starts_with("Not a safetensors"));
+    }
+
+    #[test]
+    fn matches_safetensors_extensions() {
+        let matches = |path: &str| has_safetensors_extension(Path::new(path));
+        assert!(matches("a.safetensors"));
+        assert!(matches("b/Model.SafeTensors"));
+        assert!(matches("c.sft"));
+        assert!(matches("d.SFT"));
+        assert!(!matches("e.gguf"));
+        assert!(!matches("safetensors"));
+        assert!(has_extension(Path::new("f.ZIP"), "zip"));
+        assert!(!has_extension(Path::new("g.zip.part"), "zip"));
+    }
+
+    #[test]
+    fn infers_base_family() {
+        let infer = |tensors: &[(&str, &[usize])]| parse(tensors, &[]).inferred_base();
+        assert_eq!(
+            infer(&[("lora_te1_a.lora_down.weight", &[4, 8])]),
+            Some("SDXL-based".to_string())
+        );
+        assert_eq!(
+            infer(&[("transformer.a.lora_A.weight", &[4, 8])]),
+            Some("Flux-based".to_string())
+        );
+        assert_eq!(infer(&[("lora_unet_a.lora_down.weight", &[4, 8])]), None);
+    }
+
+    #[test]
+    fn computes_text_encoder_ratio() {
+        let ratio = |tensors: &[(&str, &[usize])]| parse(tensors, &[]).text_encoder_ratio();
+        assert_eq!(ratio(&[("lora_te_a.lora_down.weight", &[4, 8])]), None);
+        assert_eq!(
+            ratio(&[
+                ("lora_unet_a.lora_down.weight", &[4, 16]),
+                ("lora_te_a.lora_down.weight", &[4, 8]),
+            ]),
+            Some(0.5)
+        );
+        // SDXL's second text encoder counts towards the text encoder side too
+        assert_eq!(
+            ratio(&[
+                ("lora_unet_a.lora_down.weight", &[4, 16]),
+                ("lora_te1_a.lora_down.weight", &[4, 8]),
+                ("lora_te2_a.lora_down.weight", &[4, 8]),
+            ]),
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn detects_frozen_text_encoder() {
+        let trains = |tensors: &[(&str, &[usize])]| parse(tensors, &[]).trains_text_encoder();
+        assert!(!trains(&[("lora_unet_a.lora_down.weight", &[4, 8])]));
+        assert!(trains(&[
+            ("lora_unet_a.lora_down.weight", &[4, 8]),
+            ("lora_te_a.lora_down.weight", &[4, 8]),
+        ]));
+        // SDXL text encoder tensors count too, including DoRA ones
+        assert!(trains(&[
+            ("lora_te1_a.lora_down.weight", &[4, 8]),
+            ("lora_te1_a.lora_up.weight", &[8, 4]),
+            ("lora_te1_a.dora_scale", &[1, 8]),
+        ]));
+    }
+
+    #[test]
+    fn classifies_checkpoints_and_vaes() {
+        let model_types = |tensors: &[(&str, &[usize])]| parse(tensors, &[]).model_types;
+        assert_eq!(
+            model_types(&[
+                ("cond_stage_model.transformer.a.weight", &[4]),
+                ("first_stage_model.decoder.a.weight", &[4]),
+            ]),
+            vec![ModelType::SdCheckpoint, ModelType::BakedVae]
+        );
+        assert_eq!(
+            model_types(&[("conditioner.embedders.0.transformer.a.weight", &[4])]),
+            vec![ModelType::SdxlCheckpoint]
+        );
+        assert_eq!(
+            model_types(&[("model.diffusion_model.joint_blocks.0.a.weight", &[4])]),
+            vec![ModelType::Sd3Checkpoint]
+        );
+        assert_eq!(
+            model_types(&[("model.diffusion_model.double_blocks.0.a.weight", &[4])]),
+            vec![ModelType::FluxCheckpoint]
+        );
+        assert_eq!(
+            model_types(&[("encoder.conv_in.weight", &[4])]),
+            vec![ModelType::StandaloneVae]
+        );
+        assert_eq!(
+            model_types(&[
+                ("transformer.a.lora_A.weight", &[4, 8]),
+                ("transformer.a.lora_B.weight", &[8, 4]),
+            ]),
+            vec![ModelType::Lora(LoraType::LoRA(NetworkType::Transformer))]
+        );
+    }
+
+    #[test]
+    fn dora_supersedes_lora() {
+        let data = parse(
+            &[
+                ("lora_te1_a.lora_down.weight", &[4, 8]),
+                ("lora_te1_a.lora_up.weight", &[8, 4]),
+                ("lora_te1_a.dora_scale", &[1, 8]),
+                ("lora_te2_b.lora_down.weight", &[4, 8]),
+            ],
+            &[],
+        );
+        assert_eq!(
+            data.model_types,
+            vec![ModelType::Lora(LoraType::DoRA(NetworkType::SdxlClip))]
+        );
+    }
+
+    #[test]
+    fn loads_files_without_metadata() {
+        // Without metadata, the model is classified from its tensors alone
+        let data = parse(
+            &[
+                ("lora_unet_a.lora_down.weight", &[4, 8]),
+                ("lora_unet_a.lora_up.weight", &[8, 4]),
+                ("lora_te_a.lora_down.weight", &[4, 8]),
+                ("lora_te_a.lora_up.weight", &[8, 4]),
+            ],
+            &[],
+        );
+        assert!(data.error.is_none());
+        assert!(data.raw_metadata.is_empty());
+        assert_eq!(
+            data.model_types,
+            vec![
+                ModelType::Lora(LoraType::LoRA(NetworkType::Unet)),
+                ModelType::Lora(LoraType::LoRA(NetworkType::SdClip)),
+            ]
+        );
+        assert_eq!(data.rank_range(), Some((4, 4)));
+        assert!(data.base_model.is_none());
+        assert!(data.network_dim.is_none());
+        assert!(data.tag_frequencies.is_empty());
+        assert!(!data.is_unrecognized());
+
+        // With no tensors either, there's nothing to go on
+        let data = parse(&[], &[]);
+        assert!(data.error.is_none());
+        assert!(data.model_types.is_empty());
+        assert!(data.is_unrecognized());
+    }
+
+    #[test]
+    fn skips_malformed_tag_counts() {
+        let metadata: HashMap<String, String> = [(
+            "ss_tag_frequency".to_string(),
+            r#"{"1_a": {"cat": 3, "dog": "2", "bird": null}, "1_b": []}"#.to_string(),
+        )]
+        .into_iter()
+        .collect();
+        let dirs = dir_tag_frequencies(&metadata, &ParseOptions::default()).unwrap();
+        assert_eq!(
+            dirs,
+            vec![(
+                "1_a".to_string(),
+                vec![("cat".to_string(), 3.0), ("dog".to_string(), 2.0)]
+            )]
+        );
+    }
+
+    #[test]
+    fn warns_about_suspicious_ranks() {
+        let data = parse(
+            &[
+                ("lora_unet_a.lora_down.weight", &[1, 320]),
+                ("lora_unet_a.lora_up.weight", &[320, 1]),
+                ("lora_unet_b.lora_down.weight", &[8, 4]),
+                ("lora_unet_b.lora_up.weight", &[4, 8]),
+            ],
+            &[("ss_network_dim", "8"), ("ss_network_alpha", "4")],
+        );
+        let warnings = data.warnings();
+        assert_eq!(warnings.len(), 3, "{warnings:?}");
+        assert!(warnings[0].contains("alpha of 4"));
+        assert!(warnings[1].starts_with("1 layer(s) have rank 1"));
+        assert!(warnings[2].contains("(rank 8, input 4)"));
+
+        let data = parse(
+            &[
+                ("lora_unet_a.alpha", &[]),
+                ("lora_unet_a.lora_down.weight", &[8, 320]),
+                ("lora_unet_a.lora_up.weight", &[320, 8]),
+            ],
+            &[("ss_network_dim", "16"), ("ss_network_alpha", "8")],
+        );
+        assert_eq!(
+            data.warnings(),
+            vec!["The metadata gives rank 16, but the tensors have rank 8"]
+        );
+
+        // A rank 1 LoRA which asked for rank 1 is fine
+        let data = parse(
+            &[
+                ("lora_unet_a.alpha", &[]),
+                ("lora_unet_a.lora_down.weight", &[1, 320]),
+                ("lora_unet_a.lora_up.weight", &[320, 1]),
+            ],
+            &[("ss_network_dim", "1"), ("ss_network_alpha", "1")],
+        );
+        assert!(data.warnings().is_empty(), "{:?}", data.warnings());
+    }
+
+    #[test]
+    fn detects_lora_fa_and_rslora() {
+        let unet =
+            |lora: fn(NetworkType) -> LoraType| vec![ModelType::Lora(lora(NetworkType::Unet))];
+        let lora: [(&str, &[usize]); 2] = [
+            ("lora_unet_a.lora_down.weight", &[4, 8]),
+            ("lora_unet_a.lora_up.weight", &[8, 4]),
+        ];
+
+        let data = parse(&lora, &[]);
+        assert_eq!(data.model_types, unet(LoraType::LoRA));
+        assert!(!data.rank_stabilized());
+        assert_eq!(
+            parse(&lora, &[("ss_network_module", "networks.lora_fa")]).model_types,
+            unet(LoraType::LoraFa)
+        );
+        // Missing down matrices are just an incomplete file, not a sign of LoRA-FA
+        assert_eq!(parse(&lora[1..], &[]).model_types, unet(LoraType::LoRA));
+        // Nor is the name turning up in the network args rather than the module
+        assert_eq!(
+            parse(&lora, &[("ss_network_args", r#"{"base": "lora_fa_v1"}"#)]).model_types,
+            unet(LoraType::LoRA)
+        );
+
+        let data = parse(
+            &lora,
+            &[("ss_network_args", r#"{"rank_stabilized": "True"}"#)],
+        );
+        assert_eq!(data.model_types, unet(LoraType::LoRA));
+        assert!(data.rank_stabilized());
+        assert!(!parse(
+            &lora,
+            &[("ss_network_args", "{'rank_stabilized': 'False'}")]
+        )
+        .rank_stabilized());
+
+        // The two can be combined, and Python literals are read as well as JSON
+        let data = parse(
+            &lora,
+            &[
+                ("ss_network_module", "networks.lora_fa"),
+                (
+                    "ss_network_args",
+                    "{'rank_stabilized': True, 'dropout': None}",
+                ),
+            ],
+        );
+        assert_eq!(data.model_types, unet(LoraType::LoraFa));
+        assert!(data.rank_stabilized());
+    }
+
+    #[test]
+    fn parses_network_args() {
+        let args = |value: &str| parse(&[], &[("ss_network_args", value)]).network_args;
+        assert_eq!(
+            args(r#"{"algo": "lokr", "conv_dim": "8", "dropout": 0.1, "use_tucker": false}"#),
+            BTreeMap::from([
+                ("algo".to_string(), "lokr".to_string()),
+                ("conv_dim".to_string(), "8".to_string()),
+                ("dropout".to_string(), "0.1".to_string()),
+                ("use_tucker".to_string(), "false".to_string()),
+            ])
+        );
+        assert!(args("not json").is_empty());
+        assert_eq!(
+            args(r#"{'algo': "lo'kr", 'dims': (4, 8), 'tucker': False, 'note': 'a \'b\''}"#),
+            BTreeMap::from([
+                ("algo".to_string(), "lo'kr".to_string()),
+                ("dims".to_string(), "[4,8]".to_string()),
+                ("note".to_string(), "a 'b'".to_string()),
+                ("tucker".to_string(), "false".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn detects_conv_layers() {
+        let attention: (&str, &[usize]) = ("lora_unet_a_attn1_to_q.lora_down.weight", &[16, 320]);
+        let conv: (&str, &[usize]) = (
+            "lora_unet_a_resnets_0_conv1.lora_down.weight",
+            &[8, 320, 3, 3],
+        );
+
+        let data = parse(&[attention], &[]);
+        assert!(!data.has_conv_layers());
+        assert_eq!(data.conv_dim(), None);
+
+        let not_conv = (
+            "lora_unet_a_convnext_attn1_to_q.lora_down.weight",
+            attention.1,
+        );
+        assert!(!parse(&[attention, not_conv], &[]).has_conv_layers());
+
+        for name in [
+            "lora_unet_conv_in.lora_down.weight",
+            "lora_unet_down_blocks_0_downsamplers_0_conv.lora_down.weight",
+            "lora_unet_a_resnets_0_conv_shortcut.lora_down.weight",
+        ] {
+            assert!(
+                parse(&[(name, &[8, 320, 3, 3])], &[]).has_conv_layers(),
+                "{name}"
+            );
+        }
+
+        let data = parse(&[attention, conv], &[]);
+        assert!(data.has_conv_layers());
+        assert_eq!(data.conv_dim(), Some(8));
+        assert_eq!(data.conv_alpha(), None);
+
+        let args = r#"{"conv_dim": "4", "conv_alpha": "1"}"#;
+        let data = parse(&[attention, conv], &[("ss_network_args", args)]);
+        assert_eq!(data.conv_dim(), Some(4));
+        assert_eq!(data.conv_alpha(), Some(1.0));
+    }
+
+    #[test]
+    fn parses_header_without_tensor_data() {
+        let buffer = model_buffer(
+            &[
+                ("lora_unet_a.lora_down.weight", &[4, 8]),
+                ("lora_unet_a.lora_up.weight", &[8, 4]),
+            ],
+            &[("ss_network_dim", "4")],
+        );
+        let header = read_header_from(&buffer[..], DEFAULT_HEADER_LIMIT).unwrap();
+        assert!(header.len() < buffer.len());
+
+        let data = LoraData::from_buffer(&header, &ParseOptions::default()).unwrap();
+        let full = LoraData::from_buffer(&buffer, &ParseOptions::default()).unwrap();
+        assert_eq!(data.tensors, full.tensors);
+        assert_eq!(data.network_dim, Some(4));
+        assert_eq!(data.file_size, buffer.len() as u64);
+        assert_eq!(data.header_size, header.len() as u64);
+    }
+
+    #[test]
+    fn normalizes_tags() {
+        assert_eq!(normalize_tag("long_hair"), "long hair");
+        assert_eq!(normalize_tag("Long Hair"), "long hair");
+        assert_eq!(normalize_tag(" long__hair "), "long hair");
+        assert_eq!(normalize_tag("1girl"), "1girl");
+        // Emoticon tags would be ruined, but they're rare enough to accept
+        assert_eq!(normalize_tag("^_^"), "^ ^");
+
+        let metadata = [(
+            "ss_tag_frequency".to_string(),
+            r#"{"1_a": {"long_hair": 3, "Long hair": 1, "long hair": 2, "smile": 1}}"#.to_string(),
+        )]
+        .into_iter()
+        .collect();
+        let tags =
+            |options: ParseOptions| dir_tag_frequencies(&metadata, &options).unwrap()[0].1.len();
+        assert_eq!(tags(ParseOptions::default()), 4);
+        let options = ParseOptions {
+            normalize_tags: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            dir_tag_frequencies(&metadata, &options).unwrap()[0].1,
+            vec![("long hair".to_string(), 6.0), ("smile".to_string(), 1.0)]
+        );
+        assert_eq!(tags(options), 2);
+    }
+
+    #[test]
+    fn finds_common_tensor_prefix() {
+        assert_eq!(
+            tensor_prefix("lora_unet_down_blocks_0.lora_down.weight"),
+            "lora_unet"
+        );
+        assert_eq!(tensor_prefix("model.diffusion_model.out.0.weight"), "model");
+        assert_eq!(tensor_prefix("base_model.model.a"), "base_model");
+        assert_eq!(tensor_prefix("weight"), "weight");
+
+        let data = parse(
+            &[
+                ("unknown.a.weight", &[4]),
+                ("unknown.b.weight", &[4]),
+                ("other.weight", &[4]),
+            ],
+            &[],
+        );
+        assert!(data.model_types.is_empty());
+        assert_eq!(data.common_tensor_prefix(), Some(("unknown", 2)));
+    }
+
+    #[test]
+    fn classifies_each_tensor() {
+        let data = parse(
+            &[
+                ("lora_unet_a.alpha", &[]),
+                ("lora_unet_a.lora_down.weight", &[4, 8]),
+            ],
+            &[],
+        );
+        assert_eq!(
+            data.tensor_types(),
+            vec![
+                ("lora_unet_a.alpha", None),
+                (
+                    "lora_unet_a.lora_down.weight",
+                    Some(ModelType::Lora(LoraType::LoRA(NetworkType::Unet)))
+                ),
+            ]
+        );
+    }
 }